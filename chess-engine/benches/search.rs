@@ -0,0 +1,93 @@
+use chess_engine::{get_best_move_parallel, perft, Board, Engine, START_FEN};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+const PERFT_DEPTH: u8 = 5;
+const SEARCH_DEPTH: u8 = 5;
+
+// An open middlegame, a tactical middlegame and an endgame
+const POSITIONS: &[(&str, &str)] = &[
+    (
+        "italian",
+        "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/3P1N2/PPP2PPP/RNBQK2R b KQkq - 0 4",
+    ),
+    (
+        "kiwipete",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    ),
+    ("rook-ending", "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1"),
+];
+
+/// Move generation speed: perft from the start position.
+fn bench_perft(c: &mut Criterion) {
+    let start = Board::from_fen(START_FEN);
+    let mut group = c.benchmark_group("perft");
+    group.sample_size(10);
+    group.bench_function(BenchmarkId::new("startpos", PERFT_DEPTH), |b| {
+        b.iter(|| perft(&start, PERFT_DEPTH))
+    });
+    group.finish();
+}
+
+/// Search speed: a fixed-depth search from scratch of each position, so
+/// every run builds the same tree.
+fn bench_search(c: &mut Criterion) {
+    let mut group = c.benchmark_group("search");
+    group.sample_size(10);
+    for (name, fen) in POSITIONS {
+        let board = Board::from_fen(fen);
+        group.bench_function(BenchmarkId::new(*name, SEARCH_DEPTH), |b| {
+            b.iter(|| {
+                let mut engine = Engine::new(board.clone());
+                engine.use_book = false;
+                engine.search(SEARCH_DEPTH, &[])
+            })
+        });
+    }
+    group.finish();
+}
+
+/// Root-splitting parallel search against the same search on one thread.
+fn bench_parallel(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parallel");
+    group.sample_size(10);
+    for (name, fen) in POSITIONS {
+        for threads in [1, 4] {
+            let id = BenchmarkId::new(*name, format!("{} threads", threads));
+            group.bench_function(id, |b| {
+                b.iter(|| get_best_move_parallel(fen, SEARCH_DEPTH, threads))
+            });
+        }
+    }
+    group.finish();
+}
+
+/// The same search with and without the pawn hash table. Both build the
+/// same tree, so the difference is the time spent scoring pawns.
+fn bench_pawn_hash(c: &mut Criterion) {
+    let mut group = c.benchmark_group("pawn-hash");
+    group.sample_size(10);
+    for (name, fen) in POSITIONS {
+        let board = Board::from_fen(fen);
+        for use_pawn_hash in [true, false] {
+            let label = if use_pawn_hash { "on" } else { "off" };
+            group.bench_function(BenchmarkId::new(*name, label), |b| {
+                b.iter(|| {
+                    let mut engine = Engine::new(board.clone());
+                    engine.use_book = false;
+                    engine.use_pawn_hash = use_pawn_hash;
+                    engine.search(SEARCH_DEPTH, &[])
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_perft,
+    bench_search,
+    bench_parallel,
+    bench_pawn_hash
+);
+criterion_main!(benches);
@@ -0,0 +1,49 @@
+use chess_engine::{perft, Board, Engine};
+use std::time::Instant;
+
+const PERFT_DEPTH: u8 = 5;
+const SEARCH_DEPTH: u8 = 6;
+
+// Start position, an open middlegame, a tactical middlegame and an endgame
+const POSITIONS: &[&str] = &[
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/3P1N2/PPP2PPP/RNBQK2R b KQkq - 0 4",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+];
+
+/// Prints node counts and speeds for perft and a fixed-depth search, as a
+/// baseline for performance changes. Run with `cargo run --release --bin bench`.
+fn main() {
+    let start = Board::from_fen(POSITIONS[0]);
+    let timer = Instant::now();
+    let nodes = perft(&start, PERFT_DEPTH);
+    report(&format!("perft {} startpos", PERFT_DEPTH), nodes, timer);
+
+    let mut total_nodes = 0;
+    let total_timer = Instant::now();
+    for fen in POSITIONS {
+        let mut engine = Engine::new(Board::from_fen(fen));
+        engine.use_book = false;
+        let timer = Instant::now();
+        engine.search(SEARCH_DEPTH, &[]);
+        report(
+            &format!("search {} {}", SEARCH_DEPTH, fen),
+            engine.nodes,
+            timer,
+        );
+        total_nodes += engine.nodes;
+    }
+    report("search total", total_nodes, total_timer);
+}
+
+fn report(label: &str, nodes: u64, timer: Instant) {
+    let secs = timer.elapsed().as_secs_f64();
+    println!(
+        "{:<90} {:>10} nodes {:>8.3} s {:>10.0} nps",
+        label,
+        nodes,
+        secs,
+        nodes as f64 / secs
+    );
+}
@@ -1,10 +1,14 @@
-use chess_engine::{get_best_move_core, Board, Color, Move, PieceType};
+use chess_engine::{
+    book_moves_in, chess960_fen, evaluate_verbose, Board, Color, Engine, EvalParams, EvalReport,
+    Game, GameStatus, Move, Piece, PieceType, Square, START_FEN,
+};
 use rustyline::completion::{Completer, Pair};
 
 use rustyline::highlight::Highlighter;
 use rustyline::hint::Hinter;
 use rustyline::validate::Validator;
 use rustyline::{Context, Helper, Result};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::{thread, time};
 
 #[derive(Clone)]
@@ -44,7 +48,6 @@ impl Highlighter for ChessHelper {}
 impl Validator for ChessHelper {}
 impl Helper for ChessHelper {}
 
-use std::collections::VecDeque;
 use std::fs::OpenOptions;
 use std::io::Write;
 
@@ -61,18 +64,167 @@ fn log_move(move_str: &str) {
     }
 }
 
+/// Set by Ctrl-C while no prompt is open, such as during autoplay. At a
+/// prompt rustyline reads Ctrl-C itself and reports `Interrupted`.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+fn install_interrupt_handler() {
+    extern "C" fn on_interrupt(_: libc::c_int) {
+        INTERRUPTED.store(true, Ordering::Relaxed);
+    }
+    // SAFETY: the handler only stores to an atomic, which is safe to do
+    // from a signal handler
+    unsafe {
+        libc::signal(
+            libc::SIGINT,
+            on_interrupt as extern "C" fn(libc::c_int) as libc::sighandler_t,
+        );
+    }
+}
+
+/// Elsewhere Ctrl-C outside a prompt still ends the program.
+#[cfg(not(unix))]
+fn install_interrupt_handler() {}
+
+/// Time each side starts with when the clock is on.
+const CLOCK_START: time::Duration = time::Duration::from_secs(5 * 60);
+
+/// Remaining thinking time for White and Black.
+struct Clock {
+    remaining: [time::Duration; 2],
+}
+
+impl Clock {
+    fn new() -> Self {
+        Clock {
+            remaining: [CLOCK_START; 2],
+        }
+    }
+
+    /// Takes `elapsed` off `color`'s time, returning false once it has run out.
+    fn charge(&mut self, color: Color, elapsed: time::Duration) -> bool {
+        let remaining = &mut self.remaining[color as usize];
+        *remaining = remaining.saturating_sub(elapsed);
+        !remaining.is_zero()
+    }
+}
+
+impl std::fmt::Display for Clock {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        // Round up so a side only shows 0:00 once it has flagged
+        let [white, black] = self.remaining.map(|d| d.as_millis().div_ceil(1000));
+        write!(
+            f,
+            "White {}:{:02} | Black {}:{:02}",
+            white / 60,
+            white % 60,
+            black / 60,
+            black % 60
+        )
+    }
+}
+
+/// Default search depth for the bot's moves and for hints.
+const BOT_DEPTH: u8 = 3;
+
+/// Search depth for spotting a forced mate after each bot move; four plies
+/// from the player's turn sees mates in up to two more bot moves.
+const MATE_SEARCH_DEPTH: u8 = 4;
+
+const USAGE: &str = "usage: console_chess [--fen <fen>] [--depth <plies>] [--color white|black]";
+
+/// How a game starts, as given on the command line.
+struct StartupConfig {
+    fen: String,
+    depth: u8,
+    color: Color,
+}
+
+/// Reads `--fen`, `--depth` and `--color` from `args`, which excludes the
+/// program name. Anything unset keeps the standard start, `BOT_DEPTH` and
+/// White.
+fn parse_args(args: &[String]) -> std::result::Result<StartupConfig, String> {
+    let mut config = StartupConfig {
+        fen: START_FEN.to_string(),
+        depth: BOT_DEPTH,
+        color: Color::White,
+    };
+    let mut args = args.iter();
+    while let Some(flag) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{} needs a value", flag));
+        match flag.as_str() {
+            "--fen" => {
+                let fen = value()?;
+                Board::try_from_fen(fen).map_err(|e| format!("invalid FEN: {}", e))?;
+                config.fen = fen.clone();
+            }
+            "--depth" => {
+                let depth = value()?;
+                config.depth = depth
+                    .parse()
+                    .ok()
+                    .filter(|&d| d > 0)
+                    .ok_or_else(|| format!("invalid depth '{}'", depth))?;
+            }
+            "--color" => {
+                config.color = match value()?.as_str() {
+                    "white" | "w" => Color::White,
+                    "black" | "b" => Color::Black,
+                    other => return Err(format!("invalid color '{}'", other)),
+                };
+            }
+            other => return Err(format!("unknown argument '{}'", other)),
+        }
+    }
+    Ok(config)
+}
+
+/// Openings `/opening` can drill, by name, as moves from the start position.
+const OPENINGS: &[(&str, &str)] = &[
+    (
+        "italian",
+        "e2e4 e7e5 g1f3 b8c6 f1c4 f8c5 c2c3 g8f6 d2d4 e5d4",
+    ),
+    (
+        "sicilian-najdorf",
+        "e2e4 c7c5 g1f3 d7d6 d2d4 c5d4 f3d4 g8f6 b1c3 a7a6",
+    ),
+    (
+        "queens-gambit-declined",
+        "d2d4 d7d5 c2c4 e7e6 b1c3 g8f6 c1g5 f8e7 e2e3 e8h8",
+    ),
+];
+
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let startup = match parse_args(&args) {
+        Ok(startup) => startup,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            std::process::exit(1);
+        }
+    };
+    let bot_depth = startup.depth;
+
     println!("Welcome to Console Chess!");
-    println!("You play as White. Enter moves as 'e2e4'.");
+    println!("You play as {}. Enter moves as 'e2e4'.", startup.color);
 
     // Setup initial board
-    let mut board = Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
-    let mut user_color = Color::White;
+    let mut start_fen = startup.fen;
+    let mut board = Board::from_fen(&start_fen);
+    let mut user_color = startup.color;
     let mut history: Vec<String> = Vec::new();
     let mut autoplay = false;
-    let mut recent_boards: VecDeque<Board> = VecDeque::with_capacity(2);
+    let bot_seed = clock_seed();
+    let mut clock: Option<Clock> = None;
+    let mut game_positions: Vec<Board> = Vec::new();
     let mut captured_white: Vec<PieceType> = Vec::new();
     let mut captured_black: Vec<PieceType> = Vec::new();
+    // The named opening the bot follows until the game leaves it
+    let mut opening: Option<(&str, &str)> = None;
+
+    install_interrupt_handler();
 
     // Clear log on start
     let _ = std::fs::write("match.log", "--- New Game ---\n");
@@ -91,7 +243,18 @@ fn main() {
             "/save".to_string(),
             "/history".to_string(),
             "/new".to_string(),
+            "/960".to_string(),
+            "/hint".to_string(),
+            "/eval".to_string(),
+            "/time".to_string(),
+            "/savegame".to_string(),
+            "/loadgame".to_string(),
+            "/edit".to_string(),
+            "/opening".to_string(),
+            "/replay".to_string(),
             "/swap".to_string(),
+            "/resign".to_string(),
+            "/draw".to_string(),
             "/autoplay".to_string(),
             "/quit".to_string(),
         ],
@@ -104,20 +267,58 @@ fn main() {
 
     loop {
         print_board(&board, history.len(), &captured_white, &captured_black);
+        if let Some(clock) = &clock {
+            println!("{}", clock);
+        }
+
+        if INTERRUPTED.swap(false, Ordering::Relaxed) {
+            if autoplay {
+                autoplay = false;
+                println!("Autoplay stopped.");
+            } else if confirm_quit(&mut rl, &start_fen, user_color, &history) {
+                break;
+            }
+        }
 
         if autoplay {
             println!("Autoplay: Swapping sides...");
             thread::sleep(time::Duration::from_secs(2));
-            user_color = if user_color == Color::White {
-                Color::Black
-            } else {
-                Color::White
-            };
+            user_color = user_color.opponent();
+        }
+
+        let draw = match board.status_with_history(&game_positions) {
+            GameStatus::FiftyMoveDraw => Some("the fifty-move rule"),
+            GameStatus::InsufficientMaterial => Some("insufficient material"),
+            GameStatus::ThreefoldRepetition => Some("threefold repetition"),
+            _ => None,
+        };
+        if let Some(reason) = draw {
+            println!("Draw by {}. Game Over.", reason);
+            break;
         }
 
-        if board.turn == user_color {
+        if board.side_to_move() == user_color {
             // User turn
+            match board.status() {
+                GameStatus::Checkmate => {
+                    println!("You are in checkmate. Game Over.");
+                    break;
+                }
+                GameStatus::Stalemate => {
+                    println!("Stalemate — draw. You have no legal moves. Game Over.");
+                    break;
+                }
+                _ => {}
+            }
+
+            let prompted = time::Instant::now();
             let readline = rl.readline("Enter move (or /help): ");
+            if let Some(clock) = &mut clock {
+                if !clock.charge(user_color, prompted.elapsed()) {
+                    println!("{} ran out of time. Game Over.", user_color);
+                    break;
+                }
+            }
             match readline {
                 Ok(line) => {
                     let input = line.trim();
@@ -132,12 +333,64 @@ fn main() {
                                 println!("  /save     - Print current FEN");
                                 println!("  /history  - Show move history");
                                 println!("  /new      - Start new game");
+                                println!("  /960      - Start new Chess960 game");
+                                println!("  /hint     - Suggest a move");
+                                println!("  /eval     - Show the engine's evaluation");
+                                println!("  /time     - Toggle a clock for each side");
+                                println!("  /savegame <path> - Save the game as PGN");
+                                println!("  /loadgame <path> - Resume a game saved as PGN");
+                                println!("  /edit     - Set up a position to play from");
+                                println!("  /opening <name> - Practise a named opening");
+                                println!("  /replay   - Step through the game so far");
                                 println!("  /swap     - Swap sides");
-                                println!("  /autoplay - Auto-swap every 2s");
+                                println!("  /resign   - Resign the game");
+                                println!("  /draw     - Offer the bot a draw");
+                                println!("  /autoplay - Auto-swap every 2s (Ctrl-C stops)");
                                 println!("  /quit     - Exit");
                             }
+                            "/time" => {
+                                clock = match clock {
+                                    Some(_) => {
+                                        println!("Clock off.");
+                                        None
+                                    }
+                                    None => {
+                                        let clock = Clock::new();
+                                        println!("Clock on: {}", clock);
+                                        Some(clock)
+                                    }
+                                };
+                            }
+                            "/hint" => {
+                                let mut engine = Engine::new(board.clone());
+                                engine.set_game_history(&game_positions);
+                                match engine.search(bot_depth, &[]) {
+                                    Some(m) => println!("Hint: {}", board.move_to_uci(&m)),
+                                    None => println!("No move to suggest."),
+                                }
+                            }
+                            "/eval" => print_eval(&board, &game_positions),
+                            "/resign" => {
+                                let result = match user_color {
+                                    Color::White => "0-1",
+                                    Color::Black => "1-0",
+                                };
+                                println!("You resign. {} Game Over.", result);
+                                offer_pgn_save(&mut rl, &start_fen, user_color, &history, result);
+                                break;
+                            }
+                            "/draw" => {
+                                if bot_accepts_draw(&board, &game_positions, bot_depth) {
+                                    println!("The bot accepts the draw. 1/2-1/2 Game Over.");
+                                    offer_pgn_save(
+                                        &mut rl, &start_fen, user_color, &history, "1/2-1/2",
+                                    );
+                                    break;
+                                }
+                                println!("The bot declines the draw.");
+                            }
                             "/save" => {
-                                let fen = board_to_fen(&board);
+                                let fen = board.to_fen();
                                 println!("Game FEN: {}", fen);
                             }
                             "/history" => {
@@ -146,68 +399,144 @@ fn main() {
                                     println!("{}. {}", i + 1, move_str);
                                 }
                             }
-                            "/new" => {
-                                board = Board::from_fen(
-                                    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
-                                );
+                            "/new" | "/960" => {
+                                start_fen = if input == "/960" {
+                                    chess960_fen(random_960_index())
+                                } else {
+                                    START_FEN.to_string()
+                                };
+                                board = Board::from_fen(&start_fen);
                                 user_color = Color::White;
                                 history.clear();
                                 autoplay = false;
-                                recent_boards.clear();
+                                game_positions.clear();
                                 captured_white.clear();
                                 captured_black.clear();
+                                opening = None;
+                                if clock.is_some() {
+                                    clock = Some(Clock::new());
+                                }
                                 let _ = std::fs::write("match.log", "--- New Game ---\n");
                                 println!("New game started.");
                             }
+                            "/edit" => match edit_position(&mut rl, &board) {
+                                Some(edited) => {
+                                    start_fen = edited.to_fen();
+                                    board = edited;
+                                    history.clear();
+                                    autoplay = false;
+                                    game_positions.clear();
+                                    captured_white.clear();
+                                    captured_black.clear();
+                                    opening = None;
+                                    if clock.is_some() {
+                                        clock = Some(Clock::new());
+                                    }
+                                    let _ = std::fs::write("match.log", "--- New Game ---\n");
+                                    println!("Playing from the edited position.");
+                                }
+                                None => println!("Edit abandoned."),
+                            },
+                            "/replay" => replay_game(&mut rl, &start_fen, &history),
                             "/swap" => {
-                                user_color = if user_color == Color::White {
-                                    Color::Black
-                                } else {
-                                    Color::White
-                                };
-                                println!("Swapped sides. You are now {:?}.", user_color);
+                                user_color = user_color.opponent();
+                                println!("Swapped sides. You are now {}.", user_color);
                             }
                             "/autoplay" => {
                                 autoplay = true;
                                 println!("Autoplay enabled. Press Ctrl-C to stop.");
                                 continue;
                             }
+                            _ if input == "/opening" || input.starts_with("/opening ") => {
+                                let name = input["/opening".len()..].trim();
+                                match OPENINGS.iter().find(|(n, _)| *n == name) {
+                                    Some(&chosen) => {
+                                        start_fen = START_FEN.to_string();
+                                        board = Board::from_fen(&start_fen);
+                                        history.clear();
+                                        autoplay = false;
+                                        game_positions.clear();
+                                        captured_white.clear();
+                                        captured_black.clear();
+                                        opening = Some(chosen);
+                                        if clock.is_some() {
+                                            clock = Some(Clock::new());
+                                        }
+                                        let _ = std::fs::write("match.log", "--- New Game ---\n");
+                                        println!(
+                                            "New game practising the {} as {}.",
+                                            chosen.0, user_color
+                                        );
+                                    }
+                                    None => {
+                                        let names: Vec<&str> =
+                                            OPENINGS.iter().map(|(n, _)| *n).collect();
+                                        println!("Openings: {}", names.join(", "));
+                                    }
+                                }
+                            }
+                            _ if input.starts_with("/savegame ") => {
+                                let path = input["/savegame ".len()..].trim();
+                                match save_game(path, &start_fen, user_color, &history) {
+                                    Ok(()) => println!("Game saved to {}.", path),
+                                    Err(e) => println!("Couldn't save game: {}", e),
+                                }
+                            }
+                            _ if input.starts_with("/loadgame ") => {
+                                let path = input["/loadgame ".len()..].trim();
+                                match load_game(path) {
+                                    Ok(saved) => {
+                                        start_fen = saved.start_fen;
+                                        user_color = saved.user_color;
+                                        board = saved.board;
+                                        history = saved.history;
+                                        game_positions = saved.positions;
+                                        captured_white = saved.captured_white;
+                                        captured_black = saved.captured_black;
+                                        opening = None;
+                                        autoplay = false;
+                                        if clock.is_some() {
+                                            clock = Some(Clock::new());
+                                        }
+                                        println!("Game loaded from {}.", path);
+                                    }
+                                    Err(e) => println!("Couldn't load game: {}", e),
+                                }
+                            }
                             _ => println!("Unknown command. Type /help for list."),
                         }
                         continue;
                     }
 
                     if let Some(m) = parse_move(input) {
-                        let legal_moves = chess_engine::generate_moves(&board);
-                        if legal_moves.contains(&m) {
-                            // Check for capture
-                            if let Some(target) = board.get_piece(m.to_row, m.to_col) {
-                                if target.color == Color::White {
-                                    captured_white.push(target.piece_type);
-                                } else {
-                                    captured_black.push(target.piece_type);
+                        if let Some(m) = find_legal_move(&board, &m) {
+                            if let Some((name, line)) = opening {
+                                let book = book_moves_in(&[line], &board);
+                                if book.is_empty() {
+                                    println!("End of the {}.", name);
+                                    opening = None;
+                                } else if !book.contains(&m) {
+                                    println!("Out of book: you left the {}.", name);
+                                    opening = None;
                                 }
                             }
-
-                            board.make_move(&m);
+                            game_positions.push(board.clone());
+                            let captured = board.make_move(&m);
+                            record_capture(captured, &mut captured_white, &mut captured_black);
                             history.push(input.to_string());
                             log_move(input);
-
-                            // Update recent boards
-                            recent_boards.push_back(board.clone());
-                            if recent_boards.len() > 2 {
-                                recent_boards.pop_front();
-                            }
                         } else {
-                            println!("Illegal move! Try again.");
+                            println!("Illegal move: {}. Try again.", board.move_legality(&m));
                         }
                     } else {
-                        println!("Invalid move format. Use 'e2e4'.");
+                        println!("Invalid move format. Use 'e2e4', or 'e7e8n' to promote.");
                     }
                 }
                 Err(rustyline::error::ReadlineError::Interrupted) => {
                     println!("CTRL-C");
-                    break;
+                    if confirm_quit(&mut rl, &start_fen, user_color, &history) {
+                        break;
+                    }
                 }
                 Err(rustyline::error::ReadlineError::Eof) => {
                     println!("CTRL-D");
@@ -221,71 +550,59 @@ fn main() {
         } else {
             // Bot turn
             println!("Bot is thinking...");
-            let mut excluded_moves = Vec::new();
-            let mut best_move = None;
-
-            // Try to find a non-repeating move
-            for _ in 0..5 {
-                // Try up to 5 times
-                let fen = board_to_fen(&board);
-                if let Some(m) = get_best_move_core(&fen, 3, &excluded_moves) {
-                    // Check if this move leads to a repeated state
-                    let mut test_board = board.clone();
-                    test_board.make_move(&m);
-
-                    // Simple repetition check: if we've seen this board state recently
-                    let is_repetition = recent_boards.iter().any(|b| {
-                        // Compare squares and turn. Ignore castling/ep for now as Board doesn't have them.
-                        b.squares
-                            .iter()
-                            .zip(test_board.squares.iter())
-                            .all(|(p1, p2)| p1 == p2)
-                            && b.turn == test_board.turn
-                    });
-
-                    if is_repetition {
-                        println!("Bot avoiding repetition...");
-                        excluded_moves.push(m);
-                        continue;
-                    }
-
-                    best_move = Some(m);
-                    break;
-                } else {
-                    break; // No more moves
+            // The engine scores repeated positions as draws, and the seed
+            // varies its choice between near-equal moves from game to game
+            let mut engine = Engine::new(board.clone());
+            engine.set_game_history(&game_positions);
+            engine.seed = Some(bot_seed);
+            let started = time::Instant::now();
+            let line_move = opening.and_then(|(_, line)| book_moves_in(&[line], &board).pop());
+            if line_move.is_none() {
+                if let Some((name, _)) = opening.take() {
+                    println!("End of the {}.", name);
                 }
             }
-
+            let in_book = line_move.is_some();
+            let best_move = line_move.or_else(|| engine.search(bot_depth, &[]));
+            let elapsed = started.elapsed();
             if let Some(m) = best_move {
-                let move_str = format!(
-                    "{}{}{}{}",
-                    (m.from_col as u8 + b'a') as char,
-                    8 - m.from_row,
-                    (m.to_col as u8 + b'a') as char,
-                    8 - m.to_row
-                );
-                println!("Bot plays: {}", move_str);
-
-                // Check for capture
-                if let Some(target) = board.get_piece(m.to_row, m.to_col) {
-                    if target.color == Color::White {
-                        captured_white.push(target.piece_type);
-                    } else {
-                        captured_black.push(target.piece_type);
+                let move_str = board.move_to_uci(&m);
+                if in_book {
+                    println!("Bot plays: {} (book)", move_str);
+                } else {
+                    println!("Bot plays: {}", move_str);
+                    println!(
+                        "Bot moved in {} ms (depth {}, {} nodes)",
+                        elapsed.as_millis(),
+                        engine.depth,
+                        engine.nodes
+                    );
+                }
+                if let Some(clock) = &mut clock {
+                    if !clock.charge(board.side_to_move(), elapsed) {
+                        println!("{} ran out of time. Game Over.", board.side_to_move());
+                        break;
                     }
                 }
 
-                board.make_move(&m);
+                game_positions.push(board.clone());
+                let captured = board.make_move(&m);
+                record_capture(captured, &mut captured_white, &mut captured_black);
                 history.push(move_str.clone());
                 log_move(&move_str);
-
-                // Update recent boards
-                recent_boards.push_back(board.clone());
-                if recent_boards.len() > 2 {
-                    recent_boards.pop_front();
+                if let Some(n) = forced_mate(&board, &game_positions) {
+                    println!("Bot has mate in {}.", n);
                 }
             } else {
-                println!("Bot has no valid moves (or all lead to repetition). Game Over.");
+                match board.status() {
+                    GameStatus::Checkmate => {
+                        println!("Checkmate! The bot has been mated. Game Over.")
+                    }
+                    GameStatus::Stalemate => {
+                        println!("Stalemate — draw. The bot has no legal moves. Game Over.")
+                    }
+                    _ => println!("Bot has no valid moves. Game Over."),
+                }
                 break;
             }
         }
@@ -300,129 +617,480 @@ fn print_board(
     captured_black: &[PieceType],
 ) {
     let move_num = history_len / 2 + 1;
-    let side = match board.turn {
+    let side = match board.side_to_move() {
         Color::White => "White",
         Color::Black => "Black",
     };
     println!("\nMove: {} | Side to play: {}", move_num, side);
 
-    // Print captured pieces
-    if !captured_white.is_empty() {
-        print!("Captured White: ");
-        for p in captured_white {
-            print!("{:?} ", p);
+    // Each side's tray holds the enemy pieces it has taken, as FEN letters
+    for (side, taken, color) in [
+        ("White", captured_black, Color::Black),
+        ("Black", captured_white, Color::White),
+    ] {
+        if !taken.is_empty() {
+            let letters: Vec<String> = taken
+                .iter()
+                .map(|&piece_type| Piece { piece_type, color }.to_string())
+                .collect();
+            println!("{} captured: {}", side, letters.join(" "));
         }
-        println!();
     }
-    if !captured_black.is_empty() {
-        print!("Captured Black: ");
-        for p in captured_black {
-            print!("{:?} ", p);
-        }
-        println!();
-    }
-
-    println!("  a b c d e f g h");
-    for r in 0..8 {
-        print!("{} ", 8 - r);
-        for c in 0..8 {
-            if let Some(p) = board.get_piece(r, c) {
-                let symbol = match (p.color, p.piece_type) {
-                    (Color::White, PieceType::Pawn) => "P",
-                    (Color::White, PieceType::Knight) => "N",
-                    (Color::White, PieceType::Bishop) => "B",
-                    (Color::White, PieceType::Rook) => "R",
-                    (Color::White, PieceType::Queen) => "Q",
-                    (Color::White, PieceType::King) => "K",
-                    (Color::Black, PieceType::Pawn) => "p",
-                    (Color::Black, PieceType::Knight) => "n",
-                    (Color::Black, PieceType::Bishop) => "b",
-                    (Color::Black, PieceType::Rook) => "r",
-                    (Color::Black, PieceType::Queen) => "q",
-                    (Color::Black, PieceType::King) => "k",
+
+    print!("{}", board.to_ascii());
+    let balance = board.material_balance() / 100;
+    match balance {
+        0 => println!("Material: even"),
+        b if b > 0 => println!("Material: White +{}", b),
+        b => println!("Material: Black +{}", -b),
+    }
+}
+
+/// Runs the position editor on a copy of `board`. Returns the edited
+/// position once `done` accepts it, or None if input ends first.
+fn edit_position(
+    rl: &mut rustyline::Editor<ChessHelper, rustyline::history::FileHistory>,
+    board: &Board,
+) -> Option<Board> {
+    println!("Editing. Commands: set <square> <piece>, clear <square>, turn w|b, done.");
+    let mut board = board.clone();
+    loop {
+        let line = rl.readline("edit> ").ok()?;
+        let args: Vec<&str> = line.split_whitespace().collect();
+        match args.as_slice() {
+            ["set", square, piece] => {
+                let Some(sq) = Square::from_algebraic(square) else {
+                    println!("Invalid square '{}'.", square);
+                    continue;
                 };
-                print!("{} ", symbol);
-            } else {
-                print!(". ");
+                let Ok(piece) = piece.parse::<Piece>() else {
+                    println!(
+                        "Invalid piece '{}'. Use a FEN letter such as Q or n.",
+                        args[2]
+                    );
+                    continue;
+                };
+                board.set_piece(sq, Some(piece));
+            }
+            ["clear", square] => {
+                let Some(sq) = Square::from_algebraic(square) else {
+                    println!("Invalid square '{}'.", square);
+                    continue;
+                };
+                board.set_piece(sq, None);
+            }
+            ["turn", "w"] => board.set_turn(Color::White),
+            ["turn", "b"] => board.set_turn(Color::Black),
+            ["done"] => match board.validate() {
+                Ok(()) => return Some(board),
+                Err(e) => {
+                    println!("Invalid position: {}", e);
+                    continue;
+                }
+            },
+            _ => {
+                println!("Unknown edit command.");
+                continue;
             }
         }
-        println!("{}", 8 - r);
+        print!("{}", board.to_ascii());
     }
-    println!("  a b c d e f g h");
 }
 
-fn parse_move(input: &str) -> Option<Move> {
-    if input.len() != 4 {
-        return None;
+/// Search depth for the score shown by `/eval`.
+const EVAL_DEPTH: u8 = 2;
+
+/// Prints the static evaluation split into material and everything else,
+/// then a shallow search score.
+fn print_eval(board: &Board, game_positions: &[Board]) {
+    let breakdown = evaluate_verbose(board, &EvalParams::default());
+    let score = breakdown.total();
+    let for_side = match board.side_to_move() {
+        Color::White => score,
+        Color::Black => -score,
+    };
+    println!(
+        "Static eval: {:+} cp for White ({:+} cp for the side to move)",
+        score, for_side
+    );
+    for (term, cp) in [
+        ("material", breakdown.material),
+        ("bishop pair", breakdown.bishop_pair),
+        ("knights and pawns", breakdown.knight_pawns),
+        ("mobility", breakdown.mobility),
+        ("pawn structure", breakdown.pawn_structure),
+        ("king safety", breakdown.king_safety),
+        ("rook placement", breakdown.rook_placement),
+        ("king proximity", breakdown.king_proximity),
+        ("basic mate", breakdown.basic_mate),
+    ] {
+        // Terms that don't apply to the position are left out
+        if cp != 0 || term == "material" {
+            println!("  {:<18} {:+} cp", term, cp);
+        }
     }
-    let chars: Vec<char> = input.chars().collect();
 
-    // Validate columns 'a'-'h'
-    if chars[0] < 'a' || chars[0] > 'h' {
-        return None;
+    let mut engine = Engine::new(board.clone());
+    engine.use_book = false;
+    engine.set_game_history(game_positions);
+    engine.search(EVAL_DEPTH, &[]);
+    match engine.score {
+        Some(EvalReport::Cp(cp)) => {
+            println!(
+                "Depth {} search: {:+} cp for the side to move",
+                EVAL_DEPTH, cp
+            )
+        }
+        Some(EvalReport::Mate(n)) => {
+            println!(
+                "Depth {} search: mate in {} for the side to move",
+                EVAL_DEPTH, n
+            )
+        }
+        None => println!("Depth {} search: no moves to search", EVAL_DEPTH),
     }
-    if chars[2] < 'a' || chars[2] > 'h' {
-        return None;
+}
+
+/// Moves until the side not to move at `board` forces mate, if a short
+/// search finds one.
+fn forced_mate(board: &Board, game_positions: &[Board]) -> Option<i32> {
+    let mut engine = Engine::new(board.clone());
+    engine.use_book = false;
+    engine.set_game_history(game_positions);
+    engine.search(MATE_SEARCH_DEPTH, &[]);
+    match engine.score {
+        Some(EvalReport::Mate(n)) if n < 0 => Some(-n),
+        _ => None,
     }
+}
+
+fn parse_move(input: &str) -> Option<Move> {
+    Move::from_coords(input)
+}
 
-    // Validate rows '1'-'8'
-    if chars[1] < '1' || chars[1] > '8' {
-        return None;
+/// The legal move matching `m`. Castling may be entered as the king's
+/// landing square (e1g1) as well as the king moving onto its rook (e1h1),
+/// and a promotion without a piece letter promotes to a queen.
+fn find_legal_move(board: &Board, m: &Move) -> Option<Move> {
+    if board.is_legal(m) {
+        return Some(m.clone());
     }
-    if chars[3] < '1' || chars[3] > '8' {
-        return None;
+    chess_engine::generate_moves(board).into_iter().find(|l| {
+        let same_from = l.from_row == m.from_row && l.from_col == m.from_col;
+        let queening = m.promotion.is_none()
+            && l.promotion == Some(PieceType::Queen)
+            && (l.to_row, l.to_col) == (m.to_row, m.to_col);
+        same_from && (queening || board.castling_king_square(l) == Some((m.to_row, m.to_col)))
+    })
+}
+
+fn record_capture(
+    captured: Option<Piece>,
+    captured_white: &mut Vec<PieceType>,
+    captured_black: &mut Vec<PieceType>,
+) {
+    if let Some(target) = captured {
+        if target.color == Color::White {
+            captured_white.push(target.piece_type);
+        } else {
+            captured_black.push(target.piece_type);
+        }
     }
+}
 
-    let from_col = (chars[0] as u8 - b'a') as usize;
-    let from_row = 8 - chars[1].to_digit(10).unwrap() as usize;
+struct SavedGame {
+    start_fen: String,
+    user_color: Color,
+    board: Board,
+    history: Vec<String>,
+    positions: Vec<Board>,
+    captured_white: Vec<PieceType>,
+    captured_black: Vec<PieceType>,
+}
 
-    let to_col = (chars[2] as u8 - b'a') as usize;
-    let to_row = 8 - chars[3].to_digit(10).unwrap() as usize;
+/// Writes the game so far as PGN, with a FEN tag when it didn't start from
+/// the standard position.
+fn save_game(
+    path: &str,
+    start_fen: &str,
+    user_color: Color,
+    history: &[String],
+) -> std::io::Result<()> {
+    std::fs::write(
+        path,
+        game_record(start_fen, user_color, history, "*").to_pgn(),
+    )
+}
 
-    Some(Move {
-        from_row,
-        from_col,
-        to_row,
-        to_col,
-    })
+/// Reads a PGN file, such as one written by `save_game`, and replays it.
+/// The user takes the side named "Player", or White in a game from
+/// elsewhere.
+fn load_game(path: &str) -> std::result::Result<SavedGame, String> {
+    let contents = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let game = Game::from_pgn(&contents).map_err(|e| e.to_string())?;
+    let tag = |name: &str| {
+        game.tags
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| value.as_str())
+    };
+    let user_color = if tag("Black") == Some("Player") {
+        Color::Black
+    } else {
+        Color::White
+    };
+
+    let mut saved = SavedGame {
+        start_fen: tag("FEN").unwrap_or(START_FEN).to_string(),
+        user_color,
+        board: game.start.clone(),
+        history: Vec::new(),
+        positions: Vec::new(),
+        captured_white: Vec::new(),
+        captured_black: Vec::new(),
+    };
+    for m in &game.moves {
+        saved.positions.push(saved.board.clone());
+        saved.history.push(saved.board.move_to_uci(m));
+        let captured = saved.board.make_move(m);
+        record_capture(
+            captured,
+            &mut saved.captured_white,
+            &mut saved.captured_black,
+        );
+    }
+    Ok(saved)
 }
 
-fn board_to_fen(board: &Board) -> String {
-    // Simplified FEN generator
-    let mut fen = String::new();
-    for r in 0..8 {
-        let mut empty = 0;
-        for c in 0..8 {
-            if let Some(p) = board.get_piece(r, c) {
-                if empty > 0 {
-                    fen.push_str(&empty.to_string());
-                    empty = 0;
-                }
-                let char = match p.piece_type {
-                    PieceType::Pawn => 'p',
-                    PieceType::Knight => 'n',
-                    PieceType::Bishop => 'b',
-                    PieceType::Rook => 'r',
-                    PieceType::Queen => 'q',
-                    PieceType::King => 'k',
-                };
-                fen.push(if p.color == Color::White {
-                    char.to_ascii_uppercase()
-                } else {
-                    char
-                });
-            } else {
-                empty += 1;
-            }
+/// The board and capture trays after the first `ply` moves of `history`.
+fn position_at(
+    start_fen: &str,
+    history: &[String],
+    ply: usize,
+) -> (Board, Vec<PieceType>, Vec<PieceType>) {
+    let mut board = Board::from_fen(start_fen);
+    let mut captured_white = Vec::new();
+    let mut captured_black = Vec::new();
+    // The history only holds moves that were legal when played
+    for move_str in &history[..ply] {
+        if let Some(m) = parse_move(move_str).and_then(|m| find_legal_move(&board, &m)) {
+            let captured = board.make_move(&m);
+            record_capture(captured, &mut captured_white, &mut captured_black);
+        }
+    }
+    (board, captured_white, captured_black)
+}
+
+/// Steps through the game from its start, rebuilding the board from the
+/// start position for each ply shown. The game itself is left untouched.
+fn replay_game(
+    rl: &mut rustyline::Editor<ChessHelper, rustyline::history::FileHistory>,
+    start_fen: &str,
+    history: &[String],
+) {
+    println!(
+        "Replaying {} moves. Commands: n (next), p (previous), q (quit).",
+        history.len()
+    );
+    let mut ply = 0;
+    loop {
+        let (board, captured_white, captured_black) = position_at(start_fen, history, ply);
+        print_board(&board, ply, &captured_white, &captured_black);
+        match ply.checked_sub(1) {
+            Some(last) => println!("Ply {}/{}: {}", ply, history.len(), history[last]),
+            None => println!("Ply 0/{}: start position", history.len()),
+        }
+
+        let Ok(line) = rl.readline("replay> ") else {
+            break;
+        };
+        match line.trim() {
+            "n" if ply < history.len() => ply += 1,
+            "n" => println!("End of the game."),
+            "p" if ply > 0 => ply -= 1,
+            "p" => println!("Start of the game."),
+            "q" => break,
+            _ => println!("Use n, p or q."),
         }
-        if empty > 0 {
-            fen.push_str(&empty.to_string());
+    }
+    println!("Replay finished.");
+}
+
+/// Centipawns either side of level within which the bot takes a draw.
+const DRAW_ACCEPT_MARGIN: i32 = 25;
+
+/// Whether the bot takes a draw offered by the player: only when its search
+/// finds the position level within `DRAW_ACCEPT_MARGIN`.
+fn bot_accepts_draw(board: &Board, game_positions: &[Board], depth: u8) -> bool {
+    let mut engine = Engine::new(board.clone());
+    engine.use_book = false;
+    engine.set_game_history(game_positions);
+    engine.search(depth, &[]);
+    matches!(engine.score, Some(EvalReport::Cp(cp)) if cp.abs() <= DRAW_ACCEPT_MARGIN)
+}
+
+/// Asks for a path and saves the game there as PGN with `result` as its
+/// result, unless the answer is empty. Returns false if Ctrl-C or end of
+/// input ended the prompt.
+fn offer_pgn_save(
+    rl: &mut rustyline::Editor<ChessHelper, rustyline::history::FileHistory>,
+    start_fen: &str,
+    user_color: Color,
+    history: &[String],
+    result: &str,
+) -> bool {
+    let Ok(path) = rl.readline("Save the game as PGN? Enter a path, or nothing to skip: ") else {
+        return false;
+    };
+    let path = path.trim();
+    if !path.is_empty() {
+        let pgn = game_record(start_fen, user_color, history, result).to_pgn();
+        match std::fs::write(path, pgn) {
+            Ok(()) => println!("Game saved to {}.", path),
+            Err(e) => println!("Couldn't save game: {}", e),
+        }
+    }
+    true
+}
+
+/// Offers to save the game as PGN after Ctrl-C, then asks whether to quit.
+/// Returns whether to quit; Ctrl-C or end of input at either prompt quits
+/// straight away.
+fn confirm_quit(
+    rl: &mut rustyline::Editor<ChessHelper, rustyline::history::FileHistory>,
+    start_fen: &str,
+    user_color: Color,
+    history: &[String],
+) -> bool {
+    if !offer_pgn_save(rl, start_fen, user_color, history, "*") {
+        return true;
+    }
+    match rl.readline("Quit? (y/n): ") {
+        Ok(answer) => answer.trim().eq_ignore_ascii_case("y"),
+        Err(_) => true,
+    }
+}
+
+/// The game so far for PGN export, with the player and the bot named as
+/// the sides they play now, and `result` as the Result tag: `*` for a game
+/// still in progress.
+fn game_record(start_fen: &str, user_color: Color, history: &[String], result: &str) -> Game {
+    let (white, black) = match user_color {
+        Color::White => ("Player", "Bot"),
+        Color::Black => ("Bot", "Player"),
+    };
+    let mut tags: Vec<(String, String)> = [
+        ("Event", "Console Chess"),
+        ("White", white),
+        ("Black", black),
+        ("Result", result),
+    ]
+    .into_iter()
+    .map(|(name, value)| (name.to_string(), value.to_string()))
+    .collect();
+    if start_fen != START_FEN {
+        tags.push(("SetUp".to_string(), "1".to_string()));
+        tags.push(("FEN".to_string(), start_fen.to_string()));
+    }
+
+    let start = Board::from_fen(start_fen);
+    let mut board = start.clone();
+    let mut moves = Vec::new();
+    // The history only holds moves that were legal when played
+    for move_str in history {
+        if let Some(m) = parse_move(move_str).and_then(|m| find_legal_move(&board, &m)) {
+            board.make_move(&m);
+            moves.push(m);
         }
-        if r < 7 {
-            fen.push('/');
+    }
+    Game {
+        tags,
+        start,
+        moves,
+        board,
+        book: None,
+    }
+}
+
+/// Pseudo-random Chess960 start index, seeded from the clock.
+fn clock_seed() -> u64 {
+    time::SystemTime::now()
+        .duration_since(time::UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0)
+}
+
+fn random_960_index() -> u16 {
+    (clock_seed() % 960) as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn no_arguments_start_the_standard_game() {
+        let config = parse_args(&[]).unwrap();
+        assert_eq!(config.fen, START_FEN);
+        assert_eq!(config.depth, BOT_DEPTH);
+        assert_eq!(config.color, Color::White);
+    }
+
+    #[test]
+    fn arguments_set_the_position_depth_and_colour() {
+        let fen = "4k3/8/8/8/8/8/4P3/4K3 b - - 0 1";
+        let config =
+            parse_args(&args(&["--fen", fen, "--depth", "6", "--color", "black"])).unwrap();
+        assert_eq!(config.fen, fen);
+        assert_eq!(config.depth, 6);
+        assert_eq!(config.color, Color::Black);
+
+        let config = parse_args(&args(&["--color", "b", "--depth", "2"])).unwrap();
+        assert_eq!(config.fen, START_FEN);
+        assert_eq!(config.depth, 2);
+        assert_eq!(config.color, Color::Black);
+    }
+
+    #[test]
+    fn bad_arguments_are_errors() {
+        for bad in [
+            &["--fen", "not a fen"][..],
+            &["--fen"][..],
+            &["--depth", "0"][..],
+            &["--depth", "deep"][..],
+            &["--color", "green"][..],
+            &["--ponder"][..],
+        ] {
+            assert!(parse_args(&args(bad)).is_err(), "{:?}", bad);
         }
     }
 
-    let turn = if board.turn == Color::White { "w" } else { "b" };
-    format!("{} {} - - 0 1", fen, turn)
+    #[test]
+    fn saved_games_load_back_from_pgn() {
+        let path = std::env::temp_dir().join(format!("console_chess_{}.pgn", std::process::id()));
+        let path = path.to_str().unwrap();
+        let start_fen = "r3k2r/pppq1ppp/2n2n2/3pp3/3PP3/2N2N2/PPPQ1PPP/R3K2R w KQkq - 0 1";
+        let history = args(&["e1g1", "d5e4", "c3e4", "e8c8"]);
+        save_game(path, start_fen, Color::Black, &history).unwrap();
+
+        let pgn = std::fs::read_to_string(path).unwrap();
+        assert!(pgn.contains(&format!("[FEN \"{}\"]", start_fen)), "{}", pgn);
+        assert!(pgn.contains("1. O-O dxe4 2. Nxe4 O-O-O"), "{}", pgn);
+
+        let saved = load_game(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(saved.start_fen, start_fen);
+        assert_eq!(saved.user_color, Color::Black);
+        assert_eq!(saved.history, history);
+        assert_eq!(saved.positions.len(), history.len());
+        assert_eq!(saved.captured_white, [PieceType::Pawn]);
+        assert_eq!(saved.captured_black, [PieceType::Pawn]);
+        let expected =
+            Board::from_fen("2kr3r/pppq1ppp/2n2n2/4p3/3PN3/5N2/PPPQ1PPP/R4RK1 w - - 1 3");
+        assert_eq!(saved.board.hash(), expected.hash());
+    }
 }
@@ -0,0 +1,138 @@
+use chess_engine::{analyze_core, Board, Move};
+use std::process::ExitCode;
+use std::time::Instant;
+
+/// Search depth when none is given.
+const DEFAULT_DEPTH: u8 = 4;
+
+/// Searches each position of an EPD file and counts those where the engine
+/// plays one of the `bm` moves. Run with
+/// `cargo run --release --bin epdtest -- <file> [depth]`.
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().collect();
+    let Some(path) = args.get(1) else {
+        eprintln!("usage: epdtest <file> [depth]");
+        return ExitCode::FAILURE;
+    };
+    let depth = match args.get(2).map(|d| d.parse()) {
+        None => DEFAULT_DEPTH,
+        Some(Ok(d)) => d,
+        Some(Err(_)) => {
+            eprintln!("invalid depth '{}'", args[2]);
+            return ExitCode::FAILURE;
+        }
+    };
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) => {
+            eprintln!("couldn't read {}: {}", path, e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let timer = Instant::now();
+    let (mut solved, mut total) = (0, 0);
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let label = format!("line {}", i + 1);
+        let position = match parse_epd(line) {
+            Ok(position) => position,
+            Err(e) => {
+                println!("skip  {}: {}", label, e);
+                continue;
+            }
+        };
+        let label = position.id.unwrap_or(label);
+        total += 1;
+
+        let analysis = analyze_core(&position.board.to_fen(), depth);
+        let found = analysis
+            .best_move
+            .as_ref()
+            .map_or("none".to_string(), |m| m.to_coords());
+        if analysis
+            .best_move
+            .is_some_and(|m| position.best_moves.contains(&m))
+        {
+            solved += 1;
+            println!("ok    {}: {}", label, found);
+        } else {
+            println!(
+                "FAIL  {}: played {}, expected {}",
+                label,
+                found,
+                position.best_move_names.join(" ")
+            );
+        }
+    }
+
+    let percent = if total == 0 {
+        0.0
+    } else {
+        solved as f64 * 100.0 / total as f64
+    };
+    println!(
+        "solved {}/{} ({:.0}%) at depth {} in {:.3} s",
+        solved,
+        total,
+        percent,
+        depth,
+        timer.elapsed().as_secs_f64()
+    );
+    ExitCode::SUCCESS
+}
+
+/// A test position and the moves that solve it.
+struct EpdPosition {
+    board: Board,
+    best_moves: Vec<Move>,
+    /// The `bm` moves as written in the file.
+    best_move_names: Vec<String>,
+    id: Option<String>,
+}
+
+/// Parses an EPD line: the first four FEN fields followed by operations
+/// such as `bm Qxf7#; id "mate 1";`. The `bm` moves may be in SAN or in
+/// coordinate notation.
+fn parse_epd(line: &str) -> Result<EpdPosition, String> {
+    let fields: Vec<&str> = line.splitn(5, char::is_whitespace).collect();
+    if fields.len() < 4 {
+        return Err("expected four FEN fields".to_string());
+    }
+    let fen = format!("{} 0 1", fields[..4].join(" "));
+    let board = Board::try_from_fen(&fen).map_err(|e| e.to_string())?;
+
+    let mut best_move_names = Vec::new();
+    let mut id = None;
+    for op in fields.get(4).unwrap_or(&"").split(';') {
+        let op = op.trim();
+        let (opcode, operands) = op.split_once(' ').unwrap_or((op, ""));
+        match opcode {
+            "bm" => best_move_names.extend(operands.split_whitespace().map(str::to_string)),
+            "id" => id = Some(operands.trim().trim_matches('"').to_string()),
+            _ => {}
+        }
+    }
+    if best_move_names.is_empty() {
+        return Err("no bm operation".to_string());
+    }
+
+    let best_moves = best_move_names
+        .iter()
+        .map(|name| {
+            board
+                .parse_san(name)
+                .or_else(|| Move::from_coords(name).filter(|m| board.is_legal(m)))
+                .ok_or_else(|| format!("illegal bm move {}", name))
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(EpdPosition {
+        board,
+        best_moves,
+        best_move_names,
+        id,
+    })
+}
@@ -0,0 +1,53 @@
+use chess_engine::{perft, perft_divide, Board};
+use std::process::ExitCode;
+use std::time::Instant;
+
+const USAGE: &str = "usage: perft <fen|startpos> <depth> [--divide]";
+
+/// Counts the leaf nodes of the move tree from a position, optionally split
+/// by root move, for checking move generation against another engine. Run
+/// with `cargo run --release --bin perft -- startpos 5 --divide`.
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let divide = args.iter().any(|a| a == "--divide");
+    let args: Vec<&String> = args.iter().filter(|a| *a != "--divide").collect();
+    let (Some(position), Some(depth)) = (args.first(), args.get(1)) else {
+        eprintln!("{}", USAGE);
+        return ExitCode::FAILURE;
+    };
+    if args.len() > 2 {
+        eprintln!("{}", USAGE);
+        return ExitCode::FAILURE;
+    }
+    let Ok(depth) = depth.parse::<u8>() else {
+        eprintln!("invalid depth '{}'", depth);
+        return ExitCode::FAILURE;
+    };
+    let board = match Board::try_from_fen(position) {
+        Ok(board) => board,
+        Err(e) => {
+            eprintln!("invalid FEN: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let timer = Instant::now();
+    let nodes = if divide {
+        let counts = perft_divide(&board, depth);
+        for (m, nodes) in &counts {
+            println!("{}: {}", board.move_to_uci(m), nodes);
+        }
+        println!();
+        counts.iter().map(|(_, nodes)| nodes).sum()
+    } else {
+        perft(&board, depth)
+    };
+    let secs = timer.elapsed().as_secs_f64();
+    println!("{}", nodes);
+    println!(
+        "{:.3} s, {:.0} nodes/s",
+        secs,
+        nodes as f64 / secs.max(1e-9)
+    );
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,376 @@
+use chess_engine::{generate_moves, Board, Color, Engine, EvalReport, Move, DEFAULT_HASH_MB};
+use std::io::{self, BufRead};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Search depth for a `go` without limits.
+const DEFAULT_DEPTH: u8 = 5;
+
+/// Largest transposition table the `Hash` option accepts, in megabytes.
+const MAX_HASH_MB: usize = 1024;
+
+/// Moves the clock is assumed to have to last for when the GUI doesn't say.
+const DEFAULT_MOVES_TO_GO: u64 = 30;
+
+/// Milliseconds kept back on every move for the time it takes to answer
+/// and for the GUI to stop the clock.
+const MOVE_OVERHEAD_MS: u64 = 50;
+
+/// A search running on its own thread, which prints `bestmove` when done.
+struct Search {
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<()>,
+}
+
+impl Search {
+    /// Asks the search to finish and waits for its `bestmove`.
+    fn stop(self) {
+        self.stop.store(true, Ordering::Relaxed);
+        let _ = self.handle.join();
+    }
+}
+
+fn main() {
+    let mut board = Board::from_fen("startpos");
+    let mut history: Vec<Board> = Vec::new();
+    let mut search: Option<Search> = None;
+    let mut hash_mb = DEFAULT_HASH_MB;
+
+    for line in io::stdin().lock().lines() {
+        let Ok(line) = line else { break };
+        let mut tokens = line.split_whitespace();
+        match tokens.next() {
+            Some("uci") => {
+                println!("id name chess-engine {}", env!("CARGO_PKG_VERSION"));
+                println!(
+                    "option name Hash type spin default {} min 1 max {}",
+                    DEFAULT_HASH_MB, MAX_HASH_MB
+                );
+                println!("uciok");
+            }
+            Some("isready") => println!("readyok"),
+            Some("setoption") => match parse_setoption(&tokens.collect::<Vec<_>>()) {
+                Some((name, value)) if name.eq_ignore_ascii_case("Hash") => {
+                    match value.parse::<usize>() {
+                        Ok(mb) => hash_mb = mb.clamp(1, MAX_HASH_MB),
+                        Err(_) => println!("info string invalid Hash value {}", value),
+                    }
+                }
+                Some((name, _)) => println!("info string unknown option {}", name),
+                None => println!("info string expected setoption name <id> value <x>"),
+            },
+            Some("ucinewgame") => {
+                stop_search(&mut search);
+                board = Board::from_fen("startpos");
+                history.clear();
+            }
+            Some("position") => {
+                stop_search(&mut search);
+                match parse_position(&tokens.collect::<Vec<_>>()) {
+                    Ok((b, h)) => {
+                        board = b;
+                        history = h;
+                    }
+                    Err(e) => println!("info string {}", e),
+                }
+            }
+            Some("go") => {
+                stop_search(&mut search);
+                let limits = parse_go(&tokens.collect::<Vec<_>>());
+                search = Some(start_search(&board, &history, limits, hash_mb));
+            }
+            Some("stop") => {
+                stop_search(&mut search);
+            }
+            Some("quit") => break,
+            _ => {}
+        }
+    }
+
+    // Don't leave a search thread running past the end of input
+    stop_search(&mut search);
+}
+
+fn stop_search(search: &mut Option<Search>) {
+    if let Some(search) = search.take() {
+        search.stop();
+    }
+}
+
+/// Parses the arguments of `position`: `startpos` or `fen <fen>`,
+/// optionally followed by `moves` and the moves played from there. Returns
+/// the resulting board and the positions before it.
+fn parse_position(args: &[&str]) -> Result<(Board, Vec<Board>), String> {
+    let moves_at = args
+        .iter()
+        .position(|&t| t == "moves")
+        .unwrap_or(args.len());
+    let mut board = match args.first() {
+        Some(&"startpos") => Board::from_fen("startpos"),
+        Some(&"fen") => {
+            Board::try_from_fen(&args[1..moves_at].join(" ")).map_err(|e| e.to_string())?
+        }
+        _ => return Err("expected startpos or fen".to_string()),
+    };
+
+    let mut history = Vec::new();
+    for token in args.iter().skip(moves_at + 1) {
+        let m = parse_move(&board, token).ok_or_else(|| format!("illegal move {}", token))?;
+        history.push(board.clone());
+        board.make_move(&m);
+    }
+    Ok((board, history))
+}
+
+/// The option name and value from the arguments of `setoption`, given as
+/// `name <id> value <x>`. Both may span several words.
+fn parse_setoption(args: &[&str]) -> Option<(String, String)> {
+    let value_at = args.iter().position(|&t| t == "value")?;
+    if args.first() != Some(&"name") || value_at < 2 {
+        return None;
+    }
+    Some((args[1..value_at].join(" "), args[value_at + 1..].join(" ")))
+}
+
+/// Limits given by the arguments of `go`.
+struct GoLimits {
+    depth: u8,
+    /// Search until `stop`, holding back `bestmove` until then.
+    infinite: bool,
+    /// Fixed time for this move, from `movetime`.
+    movetime: Option<u64>,
+    /// Milliseconds left on each side's clock.
+    wtime: Option<u64>,
+    btime: Option<u64>,
+    /// Milliseconds added to each side's clock per move.
+    winc: u64,
+    binc: u64,
+    /// Moves to play before the next time control, if there is one.
+    moves_to_go: Option<u64>,
+}
+
+impl GoLimits {
+    /// Time to spend on this move for `side`: `movetime` if given,
+    /// otherwise a budget from its clock, or `None` to search to depth.
+    fn time_budget(&self, side: Color) -> Option<Duration> {
+        let (left, increment) = match side {
+            Color::White => (self.wtime, self.winc),
+            Color::Black => (self.btime, self.binc),
+        };
+        let ms = match (self.movetime, left) {
+            (Some(movetime), _) => movetime.saturating_sub(MOVE_OVERHEAD_MS),
+            (None, Some(left)) => time_budget_ms(left, increment, self.moves_to_go),
+            (None, None) => return None,
+        };
+        Some(Duration::from_millis(ms.max(1)))
+    }
+}
+
+/// Milliseconds to spend on a move with `left` on the clock: an even share
+/// of it over the moves to go, plus the increment, but never more than the
+/// clock holds once the overhead is kept back.
+fn time_budget_ms(left: u64, increment: u64, moves_to_go: Option<u64>) -> u64 {
+    let moves = moves_to_go.unwrap_or(DEFAULT_MOVES_TO_GO).max(1);
+    let usable = left.saturating_sub(MOVE_OVERHEAD_MS);
+    (left / moves + increment).min(usable)
+}
+
+fn parse_go(args: &[&str]) -> GoLimits {
+    let mut limits = GoLimits {
+        depth: DEFAULT_DEPTH,
+        infinite: false,
+        movetime: None,
+        wtime: None,
+        btime: None,
+        winc: 0,
+        binc: 0,
+        moves_to_go: None,
+    };
+    let mut depth_given = false;
+    let mut args = args.iter();
+    while let Some(&arg) = args.next() {
+        let mut number = || args.next().and_then(|n| n.parse::<u64>().ok());
+        match arg {
+            "wtime" => limits.wtime = number(),
+            "btime" => limits.btime = number(),
+            "winc" => limits.winc = number().unwrap_or(0),
+            "binc" => limits.binc = number().unwrap_or(0),
+            "movestogo" => limits.moves_to_go = number(),
+            "movetime" => limits.movetime = number(),
+            "infinite" => {
+                limits.depth = u8::MAX;
+                limits.infinite = true;
+            }
+            "depth" => {
+                if let Some(d) = args.next().and_then(|d| d.parse().ok()) {
+                    limits.depth = d;
+                    depth_given = true;
+                }
+            }
+            // A mate in N moves is N of ours and N - 1 replies deep
+            "mate" => {
+                if let Some(n) = args.next().and_then(|n| n.parse::<u8>().ok()) {
+                    limits.depth = n.saturating_mul(2).saturating_sub(1).max(1);
+                }
+            }
+            _ => {}
+        }
+    }
+    // A timed search deepens until its time runs out, unless a depth caps it
+    let timed = limits.movetime.is_some() || limits.wtime.is_some() || limits.btime.is_some();
+    if timed && !depth_given && !limits.infinite {
+        limits.depth = u8::MAX;
+    }
+    limits
+}
+
+fn start_search(board: &Board, history: &[Board], limits: GoLimits, hash_mb: usize) -> Search {
+    let mut engine = Engine::new(board.clone());
+    engine.use_book = false;
+    engine.hash_mb = hash_mb;
+    engine.set_game_history(history);
+    engine.max_time = limits.time_budget(board.side_to_move());
+    let stop = engine.stop_flag();
+    let handle = thread::spawn(move || {
+        let (info, bestmove) = run_search(&mut engine, limits.depth);
+        if let Some(info) = info {
+            println!("{}", info);
+        }
+        if limits.infinite {
+            // The search may end early on finding a mate, but the GUI
+            // expects bestmove only after it sends stop
+            while !engine.stop_flag().load(Ordering::Relaxed) {
+                thread::sleep(Duration::from_millis(10));
+            }
+        }
+        println!("{}", bestmove);
+    });
+    Search { stop, handle }
+}
+
+/// Searches to `depth` and returns the `info` line with the score, if the
+/// search produced one, and the `bestmove` line.
+fn run_search(engine: &mut Engine, depth: u8) -> (Option<String>, String) {
+    let best_move = engine.search(depth, &[]);
+    let score = match engine.score {
+        Some(EvalReport::Cp(cp)) => Some(format!("cp {}", cp)),
+        Some(EvalReport::Mate(n)) => Some(format!("mate {}", n)),
+        None => None,
+    };
+    let info = score.map(|score| {
+        format!(
+            "info score {} nodes {} pv {}",
+            score,
+            engine.nodes,
+            format_line(&engine.board, &engine.pv)
+        )
+    });
+    let bestmove = match best_move {
+        Some(m) => format!("bestmove {}", engine.board.move_to_uci(&m)),
+        None => "bestmove 0000".to_string(),
+    };
+    (info, bestmove)
+}
+
+/// `line` in UCI notation, played out from `board`.
+fn format_line(board: &Board, line: &[Move]) -> String {
+    let mut board = board.clone();
+    let mut moves = Vec::new();
+    for m in line {
+        moves.push(board.move_to_uci(m));
+        board.make_move(m);
+    }
+    moves.join(" ")
+}
+
+/// The legal move written as `token` in UCI notation. Castling may be given
+/// as the king's landing square or as the king moving onto its rook.
+fn parse_move(board: &Board, token: &str) -> Option<Move> {
+    let m = Move::from_coords(token)?;
+    if board.is_legal(&m) {
+        return Some(m);
+    }
+    generate_moves(board).into_iter().find(|l| {
+        l.from_row == m.from_row
+            && l.from_col == m.from_col
+            && board.castling_king_square(l) == Some((m.to_row, m.to_col))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chess_engine::GameStatus;
+
+    #[test]
+    fn budget_shares_the_clock_over_the_moves_to_go() {
+        // Sudden death: a thirtieth of the clock
+        assert_eq!(time_budget_ms(60_000, 0, None), 2_000);
+        // The increment comes on top
+        assert_eq!(time_budget_ms(60_000, 1_000, None), 3_000);
+        // A time control ten moves away
+        assert_eq!(time_budget_ms(60_000, 0, Some(10)), 6_000);
+        assert_eq!(time_budget_ms(60_000, 500, Some(10)), 6_500);
+        // movestogo 0 counts as one move left
+        assert_eq!(time_budget_ms(10_000, 0, Some(0)), 9_950);
+    }
+
+    #[test]
+    fn budget_keeps_back_the_overhead_on_a_low_clock() {
+        assert_eq!(time_budget_ms(1_000, 2_000, None), 1_000 - MOVE_OVERHEAD_MS);
+        assert_eq!(time_budget_ms(400, 0, Some(1)), 400 - MOVE_OVERHEAD_MS);
+        assert_eq!(time_budget_ms(30, 0, None), 0);
+    }
+
+    #[test]
+    fn go_arguments_pick_the_side_to_moves_clock() {
+        let limits = parse_go(&[
+            "wtime", "60000", "btime", "30000", "winc", "1000", "binc", "0",
+        ]);
+        assert_eq!(
+            limits.time_budget(Color::White),
+            Some(Duration::from_millis(3_000))
+        );
+        assert_eq!(
+            limits.time_budget(Color::Black),
+            Some(Duration::from_millis(1_000))
+        );
+        let limits = parse_go(&["movetime", "500"]);
+        assert_eq!(
+            limits.time_budget(Color::White),
+            Some(Duration::from_millis(500 - MOVE_OVERHEAD_MS))
+        );
+        assert_eq!(parse_go(&["depth", "4"]).time_budget(Color::White), None);
+    }
+
+    #[test]
+    fn go_mate_finds_the_mate() {
+        // 1. Kb6 Kb8 2. Rh8#, or 1. Kc7 Ka7 2. Ra1#
+        let args: Vec<&str> = "fen k7/8/2K5/8/8/8/8/7R w - - 0 1".split(' ').collect();
+        let (board, history) = parse_position(&args).unwrap();
+        let limits = parse_go(&["mate", "2"]);
+        let mut engine = Engine::new(board.clone());
+        engine.use_book = false;
+        engine.set_game_history(&history);
+        let (info, bestmove) = run_search(&mut engine, limits.depth);
+        assert!(info.unwrap().starts_with("info score mate 2 "));
+
+        // Every reply to the move leaves a mate in one
+        let token = bestmove.strip_prefix("bestmove ").unwrap();
+        let mut board = board;
+        board.make_move(&parse_move(&board, token).unwrap());
+        let replies = generate_moves(&board);
+        assert!(!replies.is_empty());
+        for reply in replies {
+            let mut after = board.clone();
+            after.make_move(&reply);
+            assert!(generate_moves(&after).iter().any(|m| {
+                let mut mated = after.clone();
+                mated.make_move(m);
+                mated.status() == GameStatus::Checkmate
+            }));
+        }
+    }
+}
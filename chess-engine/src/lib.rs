@@ -1,9 +1,17 @@
 use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
 
+mod polyglot;
+pub use polyglot::{BookError, PolyglotBook};
+
 // Basic types
-#[derive(Clone, Copy, PartialEq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub enum PieceType {
     Pawn,
     Knight,
@@ -13,505 +21,6268 @@ pub enum PieceType {
     King,
 }
 
-#[derive(Clone, Copy, PartialEq, Debug)]
+impl PieceType {
+    pub const ALL: [PieceType; 6] = [
+        PieceType::Pawn,
+        PieceType::Knight,
+        PieceType::Bishop,
+        PieceType::Rook,
+        PieceType::Queen,
+        PieceType::King,
+    ];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    /// Material value in centipawns.
+    pub fn value(self) -> i32 {
+        PIECE_VALUES[self.index()]
+    }
+
+    /// The piece type for a FEN letter of either case, or None if `c` isn't
+    /// one.
+    pub fn from_char(c: char) -> Option<PieceType> {
+        match c.to_ascii_lowercase() {
+            'p' => Some(PieceType::Pawn),
+            'n' => Some(PieceType::Knight),
+            'b' => Some(PieceType::Bishop),
+            'r' => Some(PieceType::Rook),
+            'q' => Some(PieceType::Queen),
+            'k' => Some(PieceType::King),
+            _ => None,
+        }
+    }
+
+    /// Lowercase FEN letter.
+    pub fn to_char(self) -> char {
+        match self {
+            PieceType::Pawn => 'p',
+            PieceType::Knight => 'n',
+            PieceType::Bishop => 'b',
+            PieceType::Rook => 'r',
+            PieceType::Queen => 'q',
+            PieceType::King => 'k',
+        }
+    }
+}
+
+impl fmt::Display for PieceType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_char())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub enum Color {
     White,
     Black,
 }
 
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Color::White => write!(f, "White"),
+            Color::Black => write!(f, "Black"),
+        }
+    }
+}
+
+impl Color {
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    pub fn opponent(self) -> Color {
+        match self {
+            Color::White => Color::Black,
+            Color::Black => Color::White,
+        }
+    }
+
+    /// Row step of this side's pawn pushes. Row 0 is the eighth rank, so
+    /// White's pawns move towards lower rows.
+    pub fn forward_dir(self) -> i32 {
+        match self {
+            Color::White => -1,
+            Color::Black => 1,
+        }
+    }
+}
+
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub struct Piece {
     pub piece_type: PieceType,
     pub color: Color,
 }
 
-#[derive(Clone, PartialEq)]
-pub struct Board {
-    pub squares: [Option<Piece>; 64],
-    pub turn: Color,
-}
+impl Piece {
+    /// The piece for a FEN letter, or None if `c` isn't one.
+    pub fn from_fen_char(c: char) -> Option<Piece> {
+        let piece_type = PieceType::from_char(c)?;
+        let color = if c.is_uppercase() {
+            Color::White
+        } else {
+            Color::Black
+        };
+        Some(Piece { piece_type, color })
+    }
 
-impl Board {
-    pub fn new() -> Self {
-        // Initialize empty board
-        let squares = [None; 64];
-        Board {
-            squares,
-            turn: Color::White,
+    /// FEN letter: uppercase for White, lowercase for Black.
+    pub fn to_fen_char(self) -> char {
+        let c = self.piece_type.to_char();
+        match self.color {
+            Color::White => c.to_ascii_uppercase(),
+            Color::Black => c,
         }
     }
 
-    pub fn from_fen(fen: &str) -> Self {
-        let mut board = Board::new();
-        let parts: Vec<&str> = fen.split_whitespace().collect();
-
-        // 1. Piece placement
-        let rows: Vec<&str> = parts[0].split('/').collect();
-        for (r, row) in rows.iter().enumerate() {
-            let mut c = 0;
-            for char in row.chars() {
-                if let Some(digit) = char.to_digit(10) {
-                    c += digit as usize;
-                } else {
-                    let color = if char.is_uppercase() {
-                        Color::White
-                    } else {
-                        Color::Black
-                    };
-                    let piece_type = match char.to_ascii_lowercase() {
-                        'p' => PieceType::Pawn,
-                        'n' => PieceType::Knight,
-                        'b' => PieceType::Bishop,
-                        'r' => PieceType::Rook,
-                        'q' => PieceType::Queen,
-                        'k' => PieceType::King,
-                        _ => panic!("Invalid FEN character"),
-                    };
-                    board.squares[r * 8 + c] = Some(Piece { piece_type, color });
-                    c += 1;
-                }
-            }
+    pub fn to_unicode(self) -> char {
+        match (self.color, self.piece_type) {
+            (Color::White, PieceType::Pawn) => '♙',
+            (Color::White, PieceType::Knight) => '♘',
+            (Color::White, PieceType::Bishop) => '♗',
+            (Color::White, PieceType::Rook) => '♖',
+            (Color::White, PieceType::Queen) => '♕',
+            (Color::White, PieceType::King) => '♔',
+            (Color::Black, PieceType::Pawn) => '♟',
+            (Color::Black, PieceType::Knight) => '♞',
+            (Color::Black, PieceType::Bishop) => '♝',
+            (Color::Black, PieceType::Rook) => '♜',
+            (Color::Black, PieceType::Queen) => '♛',
+            (Color::Black, PieceType::King) => '♚',
         }
+    }
+}
 
-        // 2. Active color
-        if parts.len() > 1 {
-            board.turn = if parts[1] == "w" {
-                Color::White
-            } else {
-                Color::Black
-            };
-        }
+impl fmt::Display for Piece {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_fen_char())
+    }
+}
 
-        board
+/// A string that isn't a single FEN piece letter.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ParsePieceError(pub String);
+
+impl fmt::Display for ParsePieceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid piece '{}'", self.0)
     }
+}
 
-    pub fn get_piece(&self, row: usize, col: usize) -> Option<Piece> {
-        if row >= 8 || col >= 8 {
-            return None;
+impl std::error::Error for ParsePieceError {}
+
+impl FromStr for Piece {
+    type Err = ParsePieceError;
+
+    /// Parses a single FEN letter: uppercase for White, lowercase for Black.
+    fn from_str(s: &str) -> Result<Piece, ParsePieceError> {
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Piece::from_fen_char(c),
+            _ => None,
         }
-        self.squares[row * 8 + col]
+        .ok_or_else(|| ParsePieceError(s.to_string()))
     }
+}
 
-    pub fn make_move(&mut self, m: &Move) {
-        let piece = self.squares[m.from_row * 8 + m.from_col].take();
-        self.squares[m.to_row * 8 + m.to_col] = piece;
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GameStatus {
+    Ongoing,
+    InCheck,
+    Checkmate,
+    Stalemate,
+    FiftyMoveDraw,
+    InsufficientMaterial,
+    ThreefoldRepetition,
+}
 
-        // Pawn promotion (auto-queen for simplicity in this engine version)
-        if let Some(mut p) = self.squares[m.to_row * 8 + m.to_col] {
-            if p.piece_type == PieceType::Pawn {
-                if (p.color == Color::White && m.to_row == 0)
-                    || (p.color == Color::Black && m.to_row == 7)
-                {
-                    p.piece_type = PieceType::Queen;
-                    self.squares[m.to_row * 8 + m.to_col] = Some(p);
-                }
-            }
+impl GameStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GameStatus::Ongoing => "ongoing",
+            GameStatus::InCheck => "in_check",
+            GameStatus::Checkmate => "checkmate",
+            GameStatus::Stalemate => "stalemate",
+            GameStatus::FiftyMoveDraw => "fifty_move_draw",
+            GameStatus::InsufficientMaterial => "insufficient_material",
+            GameStatus::ThreefoldRepetition => "threefold_repetition",
         }
+    }
 
-        self.turn = match self.turn {
-            Color::White => Color::Black,
-            Color::Black => Color::White,
-        };
+    pub fn is_game_over(&self) -> bool {
+        !matches!(self, GameStatus::Ongoing | GameStatus::InCheck)
     }
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
-pub struct Move {
-    pub from_row: usize,
-    pub from_col: usize,
-    pub to_row: usize,
-    pub to_col: usize,
+/// Why a FEN string can't be read, or why the position could not have
+/// arisen in a game.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PositionError {
+    RankCount(usize),
+    /// A rank, numbered 1 to 8, whose pieces and digits don't add up to 8.
+    RankLength {
+        rank: usize,
+        files: usize,
+    },
+    InvalidPiece(char),
+    /// The en passant field isn't `-` or a square on the rank a pawn that
+    /// just double-pushed would have crossed.
+    InvalidEnPassant,
+    MissingKing(Color),
+    ExtraKing(Color),
+    PawnOnBackRank(Square),
+    OpponentInCheck,
 }
 
-impl Move {
-    #[allow(dead_code)]
-    fn to_string(&self) -> String {
-        format!(
-            "{},{},{},{}",
-            self.from_row, self.from_col, self.to_row, self.to_col
-        )
+impl fmt::Display for PositionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PositionError::RankCount(n) => write!(f, "expected 8 ranks, found {}", n),
+            PositionError::RankLength { rank, files } => {
+                write!(f, "rank {} covers {} files instead of 8", rank, files)
+            }
+            PositionError::InvalidPiece(c) => write!(f, "invalid piece letter '{}'", c),
+            PositionError::InvalidEnPassant => write!(f, "invalid en passant square"),
+            PositionError::MissingKing(color) => write!(f, "{:?} has no king", color),
+            PositionError::ExtraKing(color) => write!(f, "{:?} has more than one king", color),
+            PositionError::PawnOnBackRank(sq) => {
+                write!(f, "pawn on the back rank at {}", sq.to_algebraic())
+            }
+            PositionError::OpponentInCheck => write!(f, "the side not to move is in check"),
+        }
     }
 }
 
-// Evaluation
-const PAWN_VAL: i32 = 100;
-const KNIGHT_VAL: i32 = 320;
-const BISHOP_VAL: i32 = 330;
-const ROOK_VAL: i32 = 500;
-const QUEEN_VAL: i32 = 900;
-const KING_VAL: i32 = 20000;
+impl std::error::Error for PositionError {}
 
-fn evaluate(board: &Board) -> i32 {
-    let mut score = 0;
-    for i in 0..64 {
-        if let Some(piece) = board.squares[i] {
-            let val = match piece.piece_type {
-                PieceType::Pawn => PAWN_VAL,
-                PieceType::Knight => KNIGHT_VAL,
-                PieceType::Bishop => BISHOP_VAL,
-                PieceType::Rook => ROOK_VAL,
-                PieceType::Queen => QUEEN_VAL,
-                PieceType::King => KING_VAL,
-            };
+/// Whether a move is legal, and if not, the first reason found against it.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MoveLegality {
+    Legal,
+    NoPieceOnSource,
+    /// The piece on the origin belongs to the side not to move.
+    WrongColor,
+    OccupiedByOwnPiece,
+    /// The piece could reach the target on an empty board, but something
+    /// stands in between.
+    BlockedPath,
+    /// The piece can't reach the target however the board is set up, or the
+    /// move leaves the board.
+    NotAPseudoLegalTarget,
+    /// A promotion piece is missing on the last rank, given elsewhere, or
+    /// isn't a knight, bishop, rook or queen.
+    InvalidPromotion,
+    /// The king moving onto its own rook without the right to castle, or
+    /// through or into an attack, or with pieces in the way.
+    CannotCastle,
+    LeavesKingInCheck,
+}
 
-            if piece.color == Color::White {
-                score += val;
-            } else {
-                score -= val;
+impl fmt::Display for MoveLegality {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let reason = match self {
+            MoveLegality::Legal => "the move is legal",
+            MoveLegality::NoPieceOnSource => "there is no piece on that square",
+            MoveLegality::WrongColor => "that piece isn't yours",
+            MoveLegality::OccupiedByOwnPiece => "your own piece is on the target square",
+            MoveLegality::BlockedPath => "another piece is in the way",
+            MoveLegality::NotAPseudoLegalTarget => "that piece can't move there",
+            MoveLegality::InvalidPromotion => {
+                "a pawn promotes to a knight, bishop, rook or queen exactly when it reaches the last rank"
             }
-        }
+            MoveLegality::CannotCastle => "castling isn't allowed there",
+            MoveLegality::LeavesKingInCheck => "that move leaves your king in check",
+        };
+        f.write_str(reason)
     }
-    score
 }
 
-// Move Generation (Simplified for brevity, but functional)
-pub fn generate_moves(board: &Board) -> Vec<Move> {
-    let mut moves = Vec::new();
-    for r in 0..8 {
-        for c in 0..8 {
-            if let Some(piece) = board.get_piece(r, c) {
-                if piece.color == board.turn {
-                    // Directions
-                    let dirs = match piece.piece_type {
-                        PieceType::Pawn => Vec::new(),
-                        PieceType::Knight => vec![
-                            (-2, -1),
-                            (-2, 1),
-                            (-1, -2),
-                            (-1, 2),
-                            (1, -2),
-                            (1, 2),
-                            (2, -1),
-                            (2, 1),
-                        ],
-                        PieceType::Bishop => vec![(-1, -1), (-1, 1), (1, -1), (1, 1)],
-                        PieceType::Rook => vec![(-1, 0), (1, 0), (0, -1), (0, 1)],
-                        PieceType::Queen | PieceType::King => vec![
-                            (-1, -1),
-                            (-1, 0),
-                            (-1, 1),
-                            (0, -1),
-                            (0, 1),
-                            (1, -1),
-                            (1, 0),
-                            (1, 1),
-                        ],
-                    };
+/// A search score from the side to move's point of view.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum EvalReport {
+    /// Centipawns.
+    Cp(i32),
+    /// Moves until mate: positive when the side to move mates, negative
+    /// when it gets mated, and 0 when it is already checkmated.
+    Mate(i32),
+}
 
-                    if piece.piece_type == PieceType::Pawn {
-                        let dir = if piece.color == Color::White { -1 } else { 1 };
-                        // Move 1
-                        let r1 = (r as i32 + dir) as usize;
-                        if r1 < 8 && board.get_piece(r1, c).is_none() {
-                            moves.push(Move {
-                                from_row: r,
-                                from_col: c,
-                                to_row: r1,
-                                to_col: c,
-                            });
-                            // Move 2
-                            if (piece.color == Color::White && r == 6)
-                                || (piece.color == Color::Black && r == 1)
-                            {
-                                let r2 = (r as i32 + dir * 2) as usize;
-                                if r2 < 8 && board.get_piece(r2, c).is_none() {
-                                    moves.push(Move {
-                                        from_row: r,
-                                        from_col: c,
-                                        to_row: r2,
-                                        to_col: c,
-                                    });
-                                }
-                            }
-                        }
-                        // Captures
-                        for dc in [-1, 1] {
-                            let r_cap = (r as i32 + dir) as usize;
-                            let c_cap = (c as i32 + dc) as usize;
-                            if r_cap < 8 && c_cap < 8 {
-                                if let Some(target) = board.get_piece(r_cap, c_cap) {
-                                    if target.color != piece.color {
-                                        moves.push(Move {
-                                            from_row: r,
-                                            from_col: c,
-                                            to_row: r_cap,
-                                            to_col: c_cap,
-                                        });
-                                    }
-                                }
-                            }
-                        }
-                    } else if piece.piece_type == PieceType::Knight
-                        || piece.piece_type == PieceType::King
-                    {
-                        for (dr, dc) in dirs {
-                            let nr = r as i32 + dr;
-                            let nc = c as i32 + dc;
-                            if nr >= 0 && nr < 8 && nc >= 0 && nc < 8 {
-                                let nr = nr as usize;
-                                let nc = nc as usize;
-                                let target = board.get_piece(nr, nc);
-                                if target.is_none() || target.unwrap().color != piece.color {
-                                    moves.push(Move {
-                                        from_row: r,
-                                        from_col: c,
-                                        to_row: nr,
-                                        to_col: nc,
-                                    });
-                                }
-                            }
-                        }
-                    } else {
-                        // Sliding
-                        for (dr, dc) in dirs {
-                            let mut nr = r as i32 + dr;
-                            let mut nc = c as i32 + dc;
-                            while nr >= 0 && nr < 8 && nc >= 0 && nc < 8 {
-                                let unr = nr as usize;
-                                let unc = nc as usize;
-                                let target = board.get_piece(unr, unc);
-                                if target.is_none() {
-                                    moves.push(Move {
-                                        from_row: r,
-                                        from_col: c,
-                                        to_row: unr,
-                                        to_col: unc,
-                                    });
-                                } else {
-                                    if target.unwrap().color != piece.color {
-                                        moves.push(Move {
-                                            from_row: r,
-                                            from_col: c,
-                                            to_row: unr,
-                                            to_col: unc,
-                                        });
-                                    }
-                                    break;
-                                }
-                                nr += dr;
-                                nc += dc;
-                            }
-                        }
-                    }
-                }
-            }
+impl EvalReport {
+    /// Converts a root score; mate scores count the plies from the root to
+    /// the mated position.
+    fn from_score(score: i32) -> Self {
+        if score.abs() < MATE_THRESHOLD {
+            return EvalReport::Cp(score);
+        }
+        let plies = -MATE_SCORE - score.abs();
+        if score > 0 {
+            EvalReport::Mate((plies + 1) / 2)
+        } else {
+            EvalReport::Mate(-plies / 2)
         }
     }
 
-    moves
-        .into_iter()
-        .filter(|m| {
-            let mut b_clone = board.clone();
-            b_clone.make_move(m);
-            !is_in_check(&b_clone, board.turn)
-        })
-        .collect()
+    /// A key ordering reports from worst to best for the side to move.
+    fn rank(self) -> i32 {
+        match self {
+            EvalReport::Cp(cp) => cp,
+            EvalReport::Mate(n) if n > 0 => INFINITY - n,
+            EvalReport::Mate(n) => -INFINITY - n,
+        }
+    }
 }
 
-fn is_in_check(board: &Board, color: Color) -> bool {
-    // Find King
-    let mut king_pos = None;
-    for i in 0..64 {
-        if let Some(p) = board.squares[i] {
-            if p.piece_type == PieceType::King && p.color == color {
-                king_pos = Some(i);
-                break;
-            }
+/// What the engine makes of a position: its move, the score and the line
+/// it expects to follow.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Analysis {
+    /// `None` when the game is over.
+    pub best_move: Option<Move>,
+    pub score: EvalReport,
+    /// Principal variation, starting with `best_move`.
+    pub pv: Vec<Move>,
+}
+
+/// Default depth of a `SearchConfig`.
+const DEFAULT_SEARCH_DEPTH: u8 = 4;
+
+/// Everything that shapes a search run by `search`. Start from `Default`
+/// and set only the fields that matter, as in
+/// `SearchConfig { depth: 6, ..SearchConfig::default() }`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchConfig {
+    /// Deepest iteration to search.
+    pub depth: u8,
+    /// Ends the search after this many milliseconds, playing the best move
+    /// of the last completed iteration. Ignored under wasm, which has no
+    /// clock.
+    pub max_millis: Option<u64>,
+    /// Ends the search after this many nodes, like `max_millis` but with the
+    /// same result on every machine.
+    pub max_nodes: Option<u64>,
+    /// See `Engine::contempt`.
+    pub contempt: i32,
+    /// Play a book move without searching when the position is in the book.
+    pub use_book: bool,
+    pub eval: EvalParams,
+    /// See `Engine::seed`.
+    pub seed: Option<u64>,
+    /// Root moves not to consider.
+    pub excluded: Vec<Move>,
+    /// Size of the transposition table in megabytes.
+    pub hash_mb: usize,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        SearchConfig {
+            depth: DEFAULT_SEARCH_DEPTH,
+            max_millis: None,
+            max_nodes: None,
+            contempt: 0,
+            use_book: true,
+            eval: EvalParams::default(),
+            seed: None,
+            excluded: Vec::new(),
+            hash_mb: DEFAULT_HASH_MB,
         }
     }
+}
 
-    let king_idx = match king_pos {
-        Some(idx) => idx,
-        None => return true, // King captured
-    };
+/// The outcome of `search`.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct SearchResult {
+    /// `None` when the game is over or every move was excluded.
+    pub best_move: Option<Move>,
+    /// Score for the side to move. Drawn positions, book moves and searches
+    /// with every move excluded score `Cp(0)`.
+    pub score: EvalReport,
+    /// Principal variation, starting with `best_move`.
+    pub pv: Vec<Move>,
+    /// Nodes visited.
+    pub nodes: u64,
+    /// Depth of the last iteration completed, which is less than the depth
+    /// asked for when a limit ended the search early or a mate was found.
+    /// 0 for a book move.
+    pub depth: u8,
+    /// Time spent searching. Always 0 under wasm, which has no clock.
+    pub time_ms: u64,
+}
 
-    let kr = king_idx / 8;
-    let kc = king_idx % 8;
+/// Piece counts per side, indexed in `PieceType::ALL` order.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct PieceCounts {
+    pub white: [u32; 6],
+    pub black: [u32; 6],
+}
 
-    let opponent = if color == Color::White {
-        Color::Black
-    } else {
-        Color::White
-    };
+impl PieceCounts {
+    pub fn get(&self, color: Color, piece_type: PieceType) -> u32 {
+        match color {
+            Color::White => self.white[piece_type.index()],
+            Color::Black => self.black[piece_type.index()],
+        }
+    }
+}
 
-    // 1. Pawn attacks
-    let enemy_dir = if opponent == Color::White { -1 } else { 1 };
+/// The material on the board as piece counts per side, for recognising
+/// endgames. Displays in the usual endgame notation, White first with each
+/// side's pieces from the king down, such as `KQvK` or `KRPvKR`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct MaterialSignature {
+    counts: [[u8; 6]; 2],
+}
 
-    for dc in [-1, 1] {
-        let r = (kr as i32 - enemy_dir) as usize;
-        let c = (kc as i32 + dc) as usize;
-        if r < 8 && c < 8 {
-            if let Some(p) = board.get_piece(r, c) {
-                if p.color == opponent && p.piece_type == PieceType::Pawn {
-                    return true;
-                }
-            }
-        }
+impl MaterialSignature {
+    pub fn count(&self, color: Color, piece_type: PieceType) -> u8 {
+        self.counts[color.index()][piece_type.index()]
     }
 
-    // 2. Knight attacks
-    for (dr, dc) in [
-        (-2, -1),
-        (-2, 1),
-        (-1, -2),
-        (-1, 2),
-        (1, -2),
-        (1, 2),
-        (2, -1),
-        (2, 1),
-    ] {
-        let r = (kr as i32 + dr) as usize;
-        let c = (kc as i32 + dc) as usize;
-        if r < 8 && c < 8 {
-            if let Some(p) = board.get_piece(r, c) {
-                if p.color == opponent && p.piece_type == PieceType::Knight {
-                    return true;
-                }
-            }
-        }
+    /// Number of `color`'s pieces other than the king.
+    pub fn pieces(&self, color: Color) -> u8 {
+        self.counts[color.index()][..PieceType::King.index()]
+            .iter()
+            .sum()
     }
 
-    // 3. Sliding & King
-    let dirs = [
-        (-1, 0),
-        (1, 0),
-        (0, -1),
-        (0, 1), // Rook/Queen
-        (-1, -1),
-        (-1, 1),
-        (1, -1),
-        (1, 1), // Bishop/Queen
-    ];
+    /// Whether `color` has nothing left but its king.
+    pub fn is_bare_king(&self, color: Color) -> bool {
+        self.pieces(color) == 0
+    }
 
-    for (i, (dr, dc)) in dirs.iter().enumerate() {
-        let mut r = kr as i32 + dr;
-        let mut c = kc as i32 + dc;
+    /// The signature with the colours swapped, so `KvKQ` becomes `KQvK`.
+    pub fn mirrored(&self) -> MaterialSignature {
+        MaterialSignature {
+            counts: [self.counts[1], self.counts[0]],
+        }
+    }
+}
 
-        // First step (King check too)
-        if r >= 0 && r < 8 && c >= 0 && c < 8 {
-            if let Some(p) = board.get_piece(r as usize, c as usize) {
-                if p.color == opponent {
-                    if p.piece_type == PieceType::King {
-                        return true;
-                    }
-                    if p.piece_type == PieceType::Queen {
-                        return true;
-                    }
-                    if i < 4 && p.piece_type == PieceType::Rook {
-                        return true;
-                    }
-                    if i >= 4 && p.piece_type == PieceType::Bishop {
-                        return true;
-                    }
-                }
-                continue;
+impl fmt::Display for MaterialSignature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, color) in [Color::White, Color::Black].into_iter().enumerate() {
+            if i > 0 {
+                f.write_str("v")?;
             }
-
-            // Continue sliding
-            r += dr;
-            c += dc;
-            while r >= 0 && r < 8 && c >= 0 && c < 8 {
-                if let Some(p) = board.get_piece(r as usize, c as usize) {
-                    if p.color == opponent {
-                        if p.piece_type == PieceType::Queen {
-                            return true;
-                        }
-                        if i < 4 && p.piece_type == PieceType::Rook {
-                            return true;
-                        }
-                        if i >= 4 && p.piece_type == PieceType::Bishop {
-                            return true;
-                        }
-                    }
-                    break; // Blocked
+            for piece_type in PieceType::ALL.into_iter().rev() {
+                let letter = piece_type.to_char().to_ascii_uppercase();
+                for _ in 0..self.count(color, piece_type) {
+                    write!(f, "{}", letter)?;
                 }
-                r += dr;
-                c += dc;
             }
         }
+        Ok(())
     }
-
-    false
 }
 
-// Minimax with Alpha-Beta
-pub struct Engine {
-    pub board: Board,
-}
+// Bitboards
+//
+// Squares are indexed `row * 8 + col` with row 0 being rank 8, the same layout
+// `get_piece` uses, so bit 0 is a8 and bit 63 is h1.
 
-impl Engine {
-    pub fn new(board: Board) -> Self {
-        Engine { board }
-    }
+const KNIGHT_OFFSETS: [(i32, i32); 8] = [
+    (-2, -1),
+    (-2, 1),
+    (-1, -2),
+    (-1, 2),
+    (1, -2),
+    (1, 2),
+    (2, -1),
+    (2, 1),
+];
 
-    pub fn search(&mut self, depth: u8, excluded_moves: &[Move]) -> Option<Move> {
-        let mut best_move = None;
-        let mut best_score = -100000;
-        let alpha = -100000;
-        let beta = 100000;
+const KING_OFFSETS: [(i32, i32); 8] = [
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+    (0, -1),
+    (0, 1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+];
 
-        let mut moves = generate_moves(&self.board);
+const ROOK_DIRS: [(i32, i32); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+const BISHOP_DIRS: [(i32, i32); 4] = [(-1, -1), (-1, 1), (1, -1), (1, 1)];
 
-        // Filter excluded moves
-        if !excluded_moves.is_empty() {
-            moves.retain(|m| !excluded_moves.contains(m));
+// a8 is a light square
+const LIGHT_SQUARES: u64 = 0xAA55_AA55_AA55_AA55;
+
+const fn leaper_table(offsets: &[(i32, i32); 8]) -> [u64; 64] {
+    let mut table = [0u64; 64];
+    let mut sq = 0;
+    while sq < 64 {
+        let r = (sq / 8) as i32;
+        let c = (sq % 8) as i32;
+        let mut i = 0;
+        while i < 8 {
+            let nr = r + offsets[i].0;
+            let nc = c + offsets[i].1;
+            if nr >= 0 && nr < 8 && nc >= 0 && nc < 8 {
+                table[sq] |= 1u64 << (nr * 8 + nc);
+            }
+            i += 1;
         }
+        sq += 1;
+    }
+    table
+}
 
-        for m in moves {
-            let mut new_board = self.board.clone();
-            new_board.make_move(&m);
+static KNIGHT_ATTACKS: [u64; 64] = leaper_table(&KNIGHT_OFFSETS);
+static KING_ATTACKS: [u64; 64] = leaper_table(&KING_OFFSETS);
 
-            let score = -self.alpha_beta(&new_board, depth - 1, -beta, -alpha);
+const fn splitmix64(state: u64) -> (u64, u64) {
+    let state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    (state, z ^ (z >> 31))
+}
 
-            if score > best_score {
-                best_score = score;
-                best_move = Some(m);
+const fn zobrist_piece_table() -> [[[u64; 64]; 6]; 2] {
+    let mut table = [[[0u64; 64]; 6]; 2];
+    let mut state = 0x0123_4567_89AB_CDEF;
+    let mut color = 0;
+    while color < 2 {
+        let mut piece = 0;
+        while piece < 6 {
+            let mut sq = 0;
+            while sq < 64 {
+                let (next, key) = splitmix64(state);
+                state = next;
+                table[color][piece][sq] = key;
+                sq += 1;
             }
+            piece += 1;
         }
+        color += 1;
+    }
+    table
+}
+
+// Zobrist keys, indexed `[color][piece_type][square]`
+static ZOBRIST_PIECES: [[[u64; 64]; 6]; 2] = zobrist_piece_table();
+const ZOBRIST_BLACK_TO_MOVE: u64 = splitmix64(0xFEDC_BA98_7654_3210).1;
+// Keyed by the file of the en passant square
+static ZOBRIST_EN_PASSANT: [u64; 8] = zobrist_table(0x0F1E_2D3C_4B5A_6978);
+// Keyed by the square of a rook its side may still castle with
+static ZOBRIST_CASTLING: [u64; 64] = zobrist_table(0x8796_A5B4_C3D2_E1F0);
 
-        best_move
+const fn zobrist_table<const N: usize>(seed: u64) -> [u64; N] {
+    let mut table = [0u64; N];
+    let mut state = seed;
+    let mut i = 0;
+    while i < N {
+        let (next, key) = splitmix64(state);
+        state = next;
+        table[i] = key;
+        i += 1;
     }
+    table
+}
 
-    fn alpha_beta(&self, board: &Board, depth: u8, mut alpha: i32, beta: i32) -> i32 {
-        if depth == 0 {
-            return evaluate(board);
-        }
+/// Squares a knight on `sq` attacks, looked up from the precomputed table.
+pub fn knight_attacks(sq: usize) -> u64 {
+    KNIGHT_ATTACKS[sq]
+}
 
-        let moves = generate_moves(board);
-        if moves.is_empty() {
-            if is_in_check(board, board.turn) {
-                return -100000 + (depth as i32); // Checkmate (negative because it's bad for current player)
+/// Squares a king on `sq` attacks, looked up from the precomputed table.
+pub fn king_attacks(sq: usize) -> u64 {
+    KING_ATTACKS[sq]
+}
+
+/// Squares a pawn of `color` standing on `sq` attacks.
+fn pawn_attacks(sq: usize, color: Color) -> u64 {
+    let r = (sq / 8) as i32 + color.forward_dir();
+    let c = (sq % 8) as i32;
+    let mut attacks = 0;
+    if (0..8).contains(&r) {
+        for nc in [c - 1, c + 1] {
+            if (0..8).contains(&nc) {
+                attacks |= 1u64 << (r * 8 + nc);
             }
-            return 0; // Stalemate
         }
+    }
+    attacks
+}
 
-        let mut max_eval = -1000000;
-        for m in moves {
-            let mut b_clone = board.clone();
-            b_clone.make_move(&m);
-            let eval = -self.alpha_beta(&b_clone, depth - 1, -beta, -alpha);
-            max_eval = max_eval.max(eval);
-            alpha = alpha.max(eval);
-            if beta <= alpha {
+/// Rays from `sq` along `dirs`, each stopping at (and including) the first
+/// occupied square.
+fn sliding_attacks(sq: usize, occupied: u64, dirs: &[(i32, i32); 4]) -> u64 {
+    let mut attacks = 0;
+    for (dr, dc) in dirs {
+        let mut r = (sq / 8) as i32 + dr;
+        let mut c = (sq % 8) as i32 + dc;
+        while (0..8).contains(&r) && (0..8).contains(&c) {
+            let bit = 1u64 << (r * 8 + c);
+            attacks |= bit;
+            if occupied & bit != 0 {
                 break;
             }
+            r += dr;
+            c += dc;
         }
-        max_eval
     }
+    attacks
 }
 
-#[cfg(target_arch = "wasm32")]
-#[wasm_bindgen]
-pub fn get_best_move(fen: &str, depth: u8) -> Result<JsValue, JsValue> {
-    let best_move = get_best_move_core(fen, depth, &[]);
-    match best_move {
-        Some(m) => Ok(serde_wasm_bindgen::to_value(&m).map_err(|e| e.to_string())?),
-        None => Err(JsValue::from_str("No moves available")),
+/// Squares `piece` standing on `sq` attacks given the `occupied` squares.
+fn attacks_from(piece: Piece, sq: usize, occupied: u64) -> u64 {
+    match piece.piece_type {
+        PieceType::Pawn => pawn_attacks(sq, piece.color),
+        PieceType::Knight => KNIGHT_ATTACKS[sq],
+        PieceType::Bishop => sliding_attacks(sq, occupied, &BISHOP_DIRS),
+        PieceType::Rook => sliding_attacks(sq, occupied, &ROOK_DIRS),
+        PieceType::Queen => {
+            sliding_attacks(sq, occupied, &BISHOP_DIRS) | sliding_attacks(sq, occupied, &ROOK_DIRS)
+        }
+        PieceType::King => KING_ATTACKS[sq],
     }
 }
 
-pub fn get_best_move_core(fen: &str, depth: u8, excluded_moves: &[Move]) -> Option<Move> {
-    let board = Board::from_fen(fen);
-    let mut engine = Engine::new(board);
-    engine.search(depth, excluded_moves)
+fn file_mask(col: usize) -> u64 {
+    0x0101_0101_0101_0101 << col
+}
+
+/// Iterates over the set squares of a bitboard, lowest index first.
+struct BitIter(u64);
+
+impl Iterator for BitIter {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        if self.0 == 0 {
+            return None;
+        }
+        let sq = self.0.trailing_zeros() as usize;
+        self.0 &= self.0 - 1;
+        Some(sq)
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub struct Board {
+    /// One bitboard per piece type per colour, indexed `[color][piece_type]`.
+    /// Private so every edit goes through `put` and `remove`, which keep
+    /// the cached keys and material in step.
+    pieces: [[u64; 6]; 2],
+    /// Change with `set_turn` so the cached checkers stay in step.
+    turn: Color,
+    halfmove_clock: u32,
+    /// Home squares of the rooks each side may still castle with, indexed
+    /// `[color][KINGSIDE | QUEENSIDE]`. Storing the rook square rather than a
+    /// flag lets Chess960 positions castle with rooks on any file.
+    castling_rooks: [[Option<usize>; 2]; 2],
+    /// Square a pawn may capture onto en passant: the one crossed by an
+    /// enemy pawn's double push on the previous move. `make_move` only sets
+    /// it when a pawn stands ready to take.
+    en_passant: Option<Square>,
+    /// Zobrist key of the piece placement, kept up to date by `put` and
+    /// `remove`. The side to move is folded in by `zobrist_key`.
+    placement_key: u64,
+    /// Zobrist key of the pawns alone, kept up to date like
+    /// `placement_key`, for looking up the pawn structure score.
+    pawn_key: u64,
+    /// White material minus Black material, kept up to date like
+    /// `placement_key`.
+    material: i32,
+    /// Squares of the pieces giving check to the side to move, refreshed
+    /// whenever the placement or the side to move changes.
+    checkers: u64,
+}
+
+/// What `Board::make_null_move` changed besides the side to move, for
+/// `unmake_null_move` to restore.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NullUndo {
+    en_passant: Option<Square>,
+    halfmove_clock: u32,
+}
+
+pub const KINGSIDE: usize = 0;
+pub const QUEENSIDE: usize = 1;
+
+/// FEN of the standard initial position, which `from_fen` and
+/// `try_from_fen` also accept as `startpos`.
+pub const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+fn home_row(color: Color) -> usize {
+    match color {
+        Color::White => 7,
+        Color::Black => 0,
+    }
+}
+
+/// Where the king and rook land when `color` castles to `side`: the g- and
+/// f-files kingside, the c- and d-files queenside, whatever the start files.
+fn castling_targets(color: Color, side: usize) -> (usize, usize) {
+    let row = home_row(color) * 8;
+    if side == KINGSIDE {
+        (row + 6, row + 5)
+    } else {
+        (row + 2, row + 3)
+    }
+}
+
+/// Squares from `a` to `b` inclusive, both on the same row.
+fn row_span(a: usize, b: usize) -> u64 {
+    let (lo, hi) = (a.min(b), a.max(b));
+    (u64::MAX >> (63 - hi)) & (u64::MAX << lo)
+}
+
+impl Default for Board {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Board {
+    /// An empty board with White to move.
+    pub fn empty() -> Self {
+        Board::new()
+    }
+
+    pub fn new() -> Self {
+        // Initialize empty board
+        Board {
+            pieces: [[0; 6]; 2],
+            turn: Color::White,
+            halfmove_clock: 0,
+            castling_rooks: [[None; 2]; 2],
+            en_passant: None,
+            placement_key: 0,
+            pawn_key: 0,
+            material: 0,
+            checkers: 0,
+        }
+    }
+
+    /// Parses `fen` and rejects positions that `validate` finds illegal.
+    /// Like `from_fen`, accepts `startpos` for the initial position.
+    pub fn try_from_fen(fen: &str) -> Result<Self, PositionError> {
+        let board = Board::parse_fen(fen)?;
+        board.validate()?;
+        Ok(board)
+    }
+
+    /// Checks that each side has exactly one king, no pawn stands on the
+    /// first or eighth rank, and the side that just moved isn't in check.
+    pub fn validate(&self) -> Result<(), PositionError> {
+        for color in [Color::White, Color::Black] {
+            match self.bitboard(color, PieceType::King).count_ones() {
+                0 => return Err(PositionError::MissingKing(color)),
+                1 => {}
+                _ => return Err(PositionError::ExtraKing(color)),
+            }
+        }
+        let back_ranks = 0xFF | (0xFF << 56);
+        let pawns = (self.bitboard(Color::White, PieceType::Pawn)
+            | self.bitboard(Color::Black, PieceType::Pawn))
+            & back_ranks;
+        if let Some(sq) = BitIter(pawns).next() {
+            return Err(PositionError::PawnOnBackRank(Square(sq as u8)));
+        }
+        if is_in_check(self, self.turn.opponent()) {
+            return Err(PositionError::OpponentInCheck);
+        }
+        Ok(())
+    }
+
+    /// Parses `fen` without checking the position is legal. Fields after
+    /// the piece placement are optional, and the UCI shorthand `startpos`
+    /// stands for `START_FEN`.
+    ///
+    /// Panics if the piece placement is malformed; `try_from_fen` reports
+    /// that as an error instead.
+    pub fn from_fen(fen: &str) -> Self {
+        Board::parse_fen(fen).unwrap_or_else(|e| panic!("Invalid FEN: {}", e))
+    }
+
+    fn parse_fen(fen: &str) -> Result<Self, PositionError> {
+        let fen = match fen.trim() {
+            "startpos" => START_FEN,
+            _ => fen,
+        };
+        let mut board = Board::new();
+        let parts: Vec<&str> = fen.split_whitespace().collect();
+
+        // 1. Piece placement, checking every rank spans exactly 8 files
+        // before placing anything on it
+        let rows: Vec<&str> = parts.first().map_or(Vec::new(), |p| p.split('/').collect());
+        if rows.len() != 8 {
+            return Err(PositionError::RankCount(rows.len()));
+        }
+        for (r, row) in rows.iter().enumerate() {
+            let files: usize = row
+                .chars()
+                .map(|c| c.to_digit(10).map_or(1, |d| d as usize))
+                .sum();
+            if files != 8 {
+                return Err(PositionError::RankLength { rank: 8 - r, files });
+            }
+            let mut c = 0;
+            for char in row.chars() {
+                if let Some(digit) = char.to_digit(10) {
+                    c += digit as usize;
+                } else {
+                    let piece =
+                        Piece::from_fen_char(char).ok_or(PositionError::InvalidPiece(char))?;
+                    board.put(r * 8 + c, piece);
+                    c += 1;
+                }
+            }
+        }
+
+        // 2. Active color
+        if parts.len() > 1 {
+            board.turn = if parts[1] == "w" {
+                Color::White
+            } else {
+                Color::Black
+            };
+        }
+
+        // 3. Castling rights: KQkq, or Shredder/X-FEN rook files for Chess960
+        if parts.len() > 2 {
+            for char in parts[2].chars() {
+                let color = if char.is_uppercase() {
+                    Color::White
+                } else {
+                    Color::Black
+                };
+                board.add_castling_right(color, char.to_ascii_lowercase());
+            }
+        }
+
+        // 4. En passant square, on the rank behind the pawn that double-pushed
+        if parts.len() > 3 && parts[3] != "-" {
+            let ep_row = if board.turn == Color::White { 2 } else { 5 };
+            let sq = Square::from_algebraic(parts[3])
+                .filter(|sq| sq.index() / 8 == ep_row)
+                .ok_or(PositionError::InvalidEnPassant)?;
+            board.en_passant = Some(sq);
+        }
+
+        // 5. Halfmove clock
+        if parts.len() > 4 {
+            board.halfmove_clock = parts[4].parse().unwrap_or(0);
+        }
+
+        board.refresh_checkers();
+        Ok(board)
+    }
+
+    /// FEN for the position. Castling uses KQkq for rooks in the corners and
+    /// Shredder-FEN file letters otherwise, an en passant square is only
+    /// given when the capture is legal, and the move number is always 1.
+    pub fn to_fen(&self) -> String {
+        let mut placement = String::new();
+        for r in 0..8 {
+            let mut empty = 0;
+            for c in 0..8 {
+                match self.piece_on(r * 8 + c) {
+                    Some(p) => {
+                        if empty > 0 {
+                            placement.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        placement.push(p.to_fen_char());
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                placement.push_str(&empty.to_string());
+            }
+            if r < 7 {
+                placement.push('/');
+            }
+        }
+
+        let turn = if self.turn == Color::White { "w" } else { "b" };
+
+        let mut castling = String::new();
+        for color in [Color::White, Color::Black] {
+            for side in [KINGSIDE, QUEENSIDE] {
+                if let Some(sq) = self.castling_rooks[color.index()][side] {
+                    let letter = match (side, sq % 8) {
+                        (KINGSIDE, 7) => 'k',
+                        (QUEENSIDE, 0) => 'q',
+                        (_, col) => (b'a' + col as u8) as char,
+                    };
+                    castling.push(if color == Color::White {
+                        letter.to_ascii_uppercase()
+                    } else {
+                        letter
+                    });
+                }
+            }
+        }
+        if castling.is_empty() {
+            castling.push('-');
+        }
+
+        let en_passant = self
+            .en_passant
+            .filter(|_| self.can_capture_en_passant())
+            .map_or("-".to_string(), |sq| sq.to_algebraic());
+
+        format!(
+            "{} {} {} {} {} 1",
+            placement, turn, castling, en_passant, self.halfmove_clock
+        )
+    }
+
+    /// Records a castling right from its FEN letter: `k`/`q` pick the
+    /// outermost rook on that side of the king, a file letter names the rook.
+    fn add_castling_right(&mut self, color: Color, letter: char) {
+        let king = self.bitboard(color, PieceType::King) & (0xFF << (home_row(color) * 8));
+        if king == 0 {
+            return;
+        }
+        let ksq = king.trailing_zeros() as usize;
+        let rooks: Vec<usize> =
+            BitIter(self.bitboard(color, PieceType::Rook) & (0xFF << (home_row(color) * 8)))
+                .collect();
+        let rook = match letter {
+            'k' => rooks.iter().copied().filter(|&sq| sq > ksq).max(),
+            'q' => rooks.iter().copied().filter(|&sq| sq < ksq).min(),
+            'a'..='h' => {
+                let sq = home_row(color) * 8 + (letter as u8 - b'a') as usize;
+                rooks.contains(&sq).then_some(sq)
+            }
+            _ => None,
+        };
+        if let Some(sq) = rook {
+            let side = if sq > ksq { KINGSIDE } else { QUEENSIDE };
+            self.castling_rooks[color.index()][side] = Some(sq);
+        }
+    }
+
+    pub fn get_piece(&self, row: usize, col: usize) -> Option<Piece> {
+        if row >= 8 || col >= 8 {
+            return None;
+        }
+        self.piece_on(row * 8 + col)
+    }
+
+    pub fn piece_at(&self, sq: Square) -> Option<Piece> {
+        self.piece_on(sq.index())
+    }
+
+    /// Puts `piece` on `sq`, or empties it for `None`. Any castling right
+    /// tied to a rook on `sq` is dropped.
+    pub fn set_piece(&mut self, sq: Square, piece: Option<Piece>) {
+        self.remove(sq.index());
+        self.clear_castling_rook(sq.index());
+        if let Some(piece) = piece {
+            self.put(sq.index(), piece);
+        }
+        self.refresh_checkers();
+    }
+
+    /// The same position with the board flipped top to bottom and the
+    /// colours swapped, so White's pieces on rank 2 become Black's on rank 7
+    /// and the other side is to move. An evaluation that treats both sides
+    /// alike scores the mirrored position as the negation of this one.
+    pub fn mirrored(&self) -> Board {
+        let flip = |sq: usize| (7 - sq / 8) * 8 + sq % 8;
+        let mut board = Board::new();
+        for sq in 0..64 {
+            if let Some(p) = self.piece_on(sq) {
+                let piece = Piece {
+                    piece_type: p.piece_type,
+                    color: p.color.opponent(),
+                };
+                board.put(flip(sq), piece);
+            }
+        }
+        for color in [Color::White, Color::Black] {
+            board.castling_rooks[color.opponent().index()] =
+                self.castling_rooks[color.index()].map(|rook| rook.map(flip));
+        }
+        board.en_passant = self
+            .en_passant
+            .and_then(|sq| Square::from_index(flip(sq.index())));
+        board.halfmove_clock = self.halfmove_clock;
+        board.set_turn(self.turn.opponent());
+        board
+    }
+
+    /// Hands the move to `color`.
+    pub fn set_turn(&mut self, color: Color) {
+        self.turn = color;
+        self.refresh_checkers();
+    }
+
+    /// Bitboard of the pieces giving check to the side to move.
+    pub fn checkers(&self) -> u64 {
+        self.checkers
+    }
+
+    /// Whether the side to move is in check, read from the cached checkers.
+    /// A side with no king counts as in check, as in `is_in_check`.
+    pub fn in_check(&self) -> bool {
+        self.checkers != 0 || self.bitboard(self.turn, PieceType::King) == 0
+    }
+
+    /// Whether the side to move has nothing sharp to resolve: it isn't in
+    /// check, has no capture that wins material by static exchange and has
+    /// no pawn on its seventh rank.
+    pub fn is_quiet(&self) -> bool {
+        let seventh_row = (home_row(self.turn.opponent()) as i32 - self.turn.forward_dir()) as u64;
+        let promoting = self.bitboard(self.turn, PieceType::Pawn) & (0xFF << (seventh_row * 8));
+        !self.in_check()
+            && promoting == 0
+            && generate_captures(self).iter().all(|m| self.see(m) <= 0)
+    }
+
+    /// `color`'s pieces pinned to its king, each paired with the square of
+    /// the enemy bishop, rook or queen pinning it. Empty if `color` has no
+    /// king.
+    pub fn pinned_pieces(&self, color: Color) -> Vec<(Square, Square)> {
+        let Some(ksq) = BitIter(self.bitboard(color, PieceType::King)).next() else {
+            return Vec::new();
+        };
+        pins(self, ksq, color)
+            .map(|(pinned, pinner)| (Square(pinned as u8), Square(pinner as u8)))
+            .collect()
+    }
+
+    fn refresh_checkers(&mut self) {
+        let king = self.bitboard(self.turn, PieceType::King);
+        self.checkers = if king == 0 {
+            0
+        } else {
+            let ksq = king.trailing_zeros() as usize;
+            self.attackers_bitboard(ksq, self.turn.opponent(), self.occupied())
+        };
+    }
+
+    /// Bitboard of every piece of `piece_type` belonging to `color`.
+    pub fn bitboard(&self, color: Color, piece_type: PieceType) -> u64 {
+        self.pieces[color.index()][piece_type.index()]
+    }
+
+    /// Bitboard of every square occupied by `color`.
+    pub fn occupancy(&self, color: Color) -> u64 {
+        self.pieces[color.index()]
+            .iter()
+            .fold(0, |acc, bb| acc | bb)
+    }
+
+    /// Bitboard of every occupied square.
+    pub fn occupied(&self) -> u64 {
+        self.occupancy(Color::White) | self.occupancy(Color::Black)
+    }
+
+    pub fn side_to_move(&self) -> Color {
+        self.turn
+    }
+
+    /// Which castling rights remain, in FEN order: White kingside, White
+    /// queenside, Black kingside, Black queenside.
+    pub fn castling_rights(&self) -> [bool; 4] {
+        let [white, black] = self.castling_rooks;
+        [
+            white[KINGSIDE].is_some(),
+            white[QUEENSIDE].is_some(),
+            black[KINGSIDE].is_some(),
+            black[QUEENSIDE].is_some(),
+        ]
+    }
+
+    pub fn en_passant_square(&self) -> Option<Square> {
+        self.en_passant
+    }
+
+    /// Plies since the last capture or pawn move, for the fifty-move rule.
+    pub fn halfmove_clock(&self) -> u32 {
+        self.halfmove_clock
+    }
+
+    /// Every piece on the board with its square, a8 first.
+    pub fn pieces(&self) -> impl Iterator<Item = (Square, Piece)> + '_ {
+        self.pieces_on(self.occupied())
+    }
+
+    /// `color`'s pieces with their squares, a8 first.
+    pub fn pieces_of(&self, color: Color) -> impl Iterator<Item = (Square, Piece)> + '_ {
+        self.pieces_on(self.occupancy(color))
+    }
+
+    fn pieces_on(&self, squares: u64) -> impl Iterator<Item = (Square, Piece)> + '_ {
+        BitIter(squares).filter_map(move |sq| Some((Square(sq as u8), self.piece_on(sq)?)))
+    }
+
+    /// Zobrist key of everything that makes two positions the same for
+    /// repetition: piece placement, side to move, castling rights and the
+    /// en passant file. The en passant file only counts while the capture
+    /// is legal, as the right is otherwise meaningless.
+    fn zobrist_key(&self) -> u64 {
+        let mut key = match self.turn {
+            Color::White => self.placement_key,
+            Color::Black => self.placement_key ^ ZOBRIST_BLACK_TO_MOVE,
+        };
+        for rook in self.castling_rooks.iter().flatten().flatten() {
+            key ^= ZOBRIST_CASTLING[*rook];
+        }
+        if let Some(sq) = self.en_passant.filter(|_| self.can_capture_en_passant()) {
+            key ^= ZOBRIST_EN_PASSANT[sq.index() % 8];
+        }
+        key
+    }
+
+    /// Whether the side to move has a legal en passant capture. A pawn
+    /// beside the double-pushed one may still be pinned, or taking may
+    /// clear the rank between its king and an enemy rook.
+    fn can_capture_en_passant(&self) -> bool {
+        let Some(ep) = self.en_passant.map(|sq| sq.index()) else {
+            return false;
+        };
+        let us = self.turn;
+        let victim = (ep as i32 - us.forward_dir() * 8) as usize;
+        if self.occupied() & (1u64 << ep) != 0
+            || self.bitboard(us.opponent(), PieceType::Pawn) & (1u64 << victim) == 0
+        {
+            return false;
+        }
+        BitIter(pawn_attacks(ep, us.opponent()) & self.bitboard(us, PieceType::Pawn)).any(|from| {
+            let mut b = self.clone();
+            b.make_move(&Move {
+                from_row: from / 8,
+                from_col: from % 8,
+                to_row: ep / 8,
+                to_col: ep % 8,
+                promotion: None,
+            });
+            !is_in_check(&b, us)
+        })
+    }
+
+    /// Hash of the position, equal for positions that are the same for
+    /// repetition however they were reached: the Zobrist key of piece
+    /// placement, side to move, castling rights and a capturable en passant
+    /// square. Move counters don't affect it.
+    pub fn hash(&self) -> u64 {
+        self.zobrist_key()
+    }
+
+    fn piece_on(&self, sq: usize) -> Option<Piece> {
+        let bit = 1u64 << sq;
+        for color in [Color::White, Color::Black] {
+            for piece_type in PieceType::ALL {
+                if self.bitboard(color, piece_type) & bit != 0 {
+                    return Some(Piece { piece_type, color });
+                }
+            }
+        }
+        None
+    }
+
+    fn put(&mut self, sq: usize, piece: Piece) {
+        self.pieces[piece.color.index()][piece.piece_type.index()] |= 1u64 << sq;
+        self.update_caches(sq, piece, 1);
+    }
+
+    fn remove(&mut self, sq: usize) -> Option<Piece> {
+        let piece = self.piece_on(sq)?;
+        self.pieces[piece.color.index()][piece.piece_type.index()] &= !(1u64 << sq);
+        self.update_caches(sq, piece, -1);
+        Some(piece)
+    }
+
+    /// Toggles `piece` on `sq` in the placement and pawn keys and adds
+    /// (`sign` 1) or takes away (`sign` -1) its material.
+    fn update_caches(&mut self, sq: usize, piece: Piece, sign: i32) {
+        let key = ZOBRIST_PIECES[piece.color.index()][piece.piece_type.index()][sq];
+        self.placement_key ^= key;
+        if piece.piece_type == PieceType::Pawn {
+            self.pawn_key ^= key;
+        }
+        let value = piece.piece_type.value() * sign;
+        match piece.color {
+            Color::White => self.material += value,
+            Color::Black => self.material -= value,
+        }
+    }
+
+    /// Whether `m` castles. Castling is encoded as the king moving onto its
+    /// own rook, which stays unambiguous in Chess960 where the king may
+    /// start on or next to its destination square.
+    pub fn is_castling(&self, m: &Move) -> bool {
+        match (
+            self.get_piece(m.from_row, m.from_col),
+            self.get_piece(m.to_row, m.to_col),
+        ) {
+            (Some(king), Some(rook)) => {
+                king.piece_type == PieceType::King
+                    && rook.piece_type == PieceType::Rook
+                    && king.color == rook.color
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether `m` is a pawn capturing en passant.
+    pub fn is_en_passant(&self, m: &Move) -> bool {
+        self.en_passant == Some(m.to_square())
+            && self
+                .get_piece(m.from_row, m.from_col)
+                .is_some_and(|p| p.piece_type == PieceType::Pawn)
+    }
+
+    /// The piece `m` captures, counting en passant and not the rook a
+    /// castling king moves onto.
+    pub fn captured_piece(&self, m: &Move) -> Option<Piece> {
+        if self.is_castling(m) {
+            return None;
+        }
+        if self.is_en_passant(m) {
+            return self.get_piece(m.from_row, m.to_col);
+        }
+        self.get_piece(m.to_row, m.to_col)
+    }
+
+    /// Whether `m` captures a piece, en passant included.
+    pub fn is_capture(&self, m: &Move) -> bool {
+        self.captured_piece(m).is_some()
+    }
+
+    /// Classifies `m`, which should be legal on this board.
+    pub fn classify(&self, m: &Move) -> MoveKind {
+        if self.is_castling(m) {
+            return MoveKind::Castle;
+        }
+        if self.is_en_passant(m) {
+            return MoveKind::EnPassant;
+        }
+        let capture = self.get_piece(m.to_row, m.to_col).is_some();
+        let pawn = self
+            .get_piece(m.from_row, m.from_col)
+            .is_some_and(|p| p.piece_type == PieceType::Pawn);
+        if pawn && (m.to_row == 0 || m.to_row == 7) {
+            MoveKind::Promotion { capture }
+        } else if capture {
+            MoveKind::Capture
+        } else if pawn && m.from_row.abs_diff(m.to_row) == 2 {
+            MoveKind::DoublePawnPush
+        } else {
+            MoveKind::Quiet
+        }
+    }
+
+    /// The square `(row, col)` the king lands on for a castling move.
+    pub fn castling_king_square(&self, m: &Move) -> Option<(usize, usize)> {
+        if !self.is_castling(m) {
+            return None;
+        }
+        Some((m.from_row, if m.to_col > m.from_col { 6 } else { 2 }))
+    }
+
+    /// Coordinate notation for `m` as UCI and other engines write it:
+    /// castling as the king's landing square unless the king stays put, as
+    /// it may in Chess960, and the promotion piece as a suffix.
+    pub fn move_to_uci(&self, m: &Move) -> String {
+        let (to_row, to_col) = self
+            .castling_king_square(m)
+            .filter(|&sq| sq != (m.from_row, m.from_col))
+            .unwrap_or((m.to_row, m.to_col));
+        let shown = Move {
+            to_row,
+            to_col,
+            ..m.clone()
+        };
+        shown.to_coords()
+    }
+
+    fn make_castling_move(&mut self, m: &Move) {
+        let color = self.turn;
+        let side = if m.to_col > m.from_col {
+            KINGSIDE
+        } else {
+            QUEENSIDE
+        };
+        let (king_to, rook_to) = castling_targets(color, side);
+
+        // Lift both pieces first as the landing squares may overlap the start squares
+        self.remove(m.from_row * 8 + m.from_col);
+        self.remove(m.to_row * 8 + m.to_col);
+        self.put(
+            king_to,
+            Piece {
+                piece_type: PieceType::King,
+                color,
+            },
+        );
+        self.put(
+            rook_to,
+            Piece {
+                piece_type: PieceType::Rook,
+                color,
+            },
+        );
+        self.castling_rooks[color.index()] = [None; 2];
+        self.halfmove_clock += 1;
+    }
+
+    /// Drops any castling right tied to a rook on `sq`.
+    fn clear_castling_rook(&mut self, sq: usize) {
+        for rights in self.castling_rooks.iter_mut() {
+            for rook in rights.iter_mut() {
+                if *rook == Some(sq) {
+                    *rook = None;
+                }
+            }
+        }
+    }
+
+    /// Plays `m`, which should be legal, and returns the piece it captured.
+    pub fn make_move(&mut self, m: &Move) -> Option<Piece> {
+        let en_passant = self.is_en_passant(m);
+        self.en_passant = None;
+        if self.is_castling(m) {
+            self.make_castling_move(m);
+            self.turn = self.turn.opponent();
+            self.refresh_checkers();
+            return None;
+        }
+
+        let piece = self.remove(m.from_row * 8 + m.from_col);
+        let mut captured = self.remove(m.to_row * 8 + m.to_col);
+        if en_passant {
+            // The captured pawn stands beside the capturing one, not on the
+            // square it lands on
+            captured = self.remove(m.from_row * 8 + m.to_col);
+        }
+
+        // A king move gives up both castling rights; moving a rook or
+        // capturing it on its home square gives up that side's right, even
+        // though the king never moved
+        if let Some(p) = piece.filter(|p| p.piece_type == PieceType::King) {
+            self.castling_rooks[p.color.index()] = [None; 2];
+        }
+        self.clear_castling_rook(m.from_row * 8 + m.from_col);
+        self.clear_castling_rook(m.to_row * 8 + m.to_col);
+
+        // Fifty-move rule: reset on pawn moves and captures
+        if captured.is_some() || piece.is_some_and(|p| p.piece_type == PieceType::Pawn) {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
+        }
+
+        if let Some(mut p) = piece {
+            // A pawn promotes on the opponent's back rank: row 0 for White
+            if p.piece_type == PieceType::Pawn && m.to_row == home_row(p.color.opponent()) {
+                p.piece_type = m.promotion.unwrap_or(PieceType::Queen);
+            }
+            self.put(m.to_row * 8 + m.to_col, p);
+
+            // After a double push, record the square crossed if an enemy
+            // pawn could capture onto it
+            if p.piece_type == PieceType::Pawn && m.from_row.abs_diff(m.to_row) == 2 {
+                let crossed = (m.from_row + m.to_row) / 2 * 8 + m.to_col;
+                let them = p.color.opponent();
+                if pawn_attacks(crossed, p.color) & self.bitboard(them, PieceType::Pawn) != 0 {
+                    self.en_passant = Square::from_index(crossed);
+                }
+            }
+        }
+
+        self.turn = self.turn.opponent();
+        self.refresh_checkers();
+        captured
+    }
+
+    /// Passes the move to the other side without moving a piece, as null
+    /// move pruning and "what if I pass" analysis need. The en passant
+    /// square lapses and the halfmove clock counts on as for a quiet move.
+    /// Passing while in check leaves the side that passed able to capture
+    /// the king, so callers should only pass out of check.
+    pub fn make_null_move(&mut self) -> NullUndo {
+        let undo = NullUndo {
+            en_passant: self.en_passant.take(),
+            halfmove_clock: self.halfmove_clock,
+        };
+        self.halfmove_clock += 1;
+        self.set_turn(self.turn.opponent());
+        undo
+    }
+
+    /// Takes back the null move `undo` came from.
+    pub fn unmake_null_move(&mut self, undo: NullUndo) {
+        self.en_passant = undo.en_passant;
+        self.halfmove_clock = undo.halfmove_clock;
+        self.set_turn(self.turn.opponent());
+    }
+
+    /// The board from White's side using FEN letters, with rank and file labels.
+    pub fn to_ascii(&self) -> String {
+        self.render(Color::White, false)
+    }
+
+    /// The board from White's side using Unicode chess glyphs.
+    pub fn to_unicode(&self) -> String {
+        self.render(Color::White, true)
+    }
+
+    /// Renders the board as seen from `orientation`'s side, one line per rank
+    /// with empty squares shown as `.`.
+    pub fn render(&self, orientation: Color, unicode: bool) -> String {
+        let flip = |i: usize| match orientation {
+            Color::White => i,
+            Color::Black => 7 - i,
+        };
+        let files: String = (0..8)
+            .map(|c| format!(" {}", (b'a' + flip(c) as u8) as char))
+            .collect();
+        let mut out = format!(" {}\n", files);
+        for r in (0..8).map(flip) {
+            out.push_str(&format!("{} ", 8 - r));
+            for c in (0..8).map(flip) {
+                let symbol = match self.get_piece(r, c) {
+                    Some(p) if unicode => p.to_unicode(),
+                    Some(p) => p.to_fen_char(),
+                    None => '.',
+                };
+                out.push(symbol);
+                out.push(' ');
+            }
+            out.push_str(&format!("{}\n", 8 - r));
+        }
+        out.push_str(&format!(" {}\n", files));
+        out
+    }
+
+    /// Static exchange evaluation of `m`: the material the side to move
+    /// gains on the destination square if both sides keep recapturing with
+    /// their least valuable attacker, each free to stop when that pays.
+    pub fn see(&self, m: &Move) -> i32 {
+        let from = m.from_square().index();
+        let to = m.to_square().index();
+        let Some(mover) = self.piece_on(from) else {
+            return 0;
+        };
+        if self.is_castling(m) {
+            return 0;
+        }
+
+        let mut gain = [0i32; 33];
+        gain[0] = self.captured_piece(m).map_or(0, |p| p.piece_type.value());
+        let mut depth = 0;
+        let mut occupied = self.occupied();
+        let mut attacker_sq = from;
+        let mut attacker_value = mover.piece_type.value();
+        let mut side = mover.color;
+
+        loop {
+            depth += 1;
+            // Speculative score for the other side should it recapture
+            gain[depth] = attacker_value - gain[depth - 1];
+            occupied &= !(1u64 << attacker_sq);
+            side = side.opponent();
+
+            // Recomputing attacks against the shrinking occupancy reveals
+            // sliders x-raying through the pieces already traded off
+            let attackers = self.attackers_bitboard(to, side, occupied) & occupied;
+            let Some(piece_type) = PieceType::ALL
+                .into_iter()
+                .find(|&pt| attackers & self.bitboard(side, pt) != 0)
+            else {
+                break;
+            };
+            attacker_sq = (attackers & self.bitboard(side, piece_type)).trailing_zeros() as usize;
+            attacker_value = piece_type.value();
+        }
+
+        while depth > 1 {
+            depth -= 1;
+            gain[depth - 1] = -(-gain[depth - 1]).max(gain[depth]);
+        }
+        gain[0]
+    }
+
+    /// Bitboard of `by`'s pieces attacking `sq`, with sliders blocked by `occupied`.
+    fn attackers_bitboard(&self, sq: usize, by: Color, occupied: u64) -> u64 {
+        let defender = by.opponent();
+        let queens = self.bitboard(by, PieceType::Queen);
+        (pawn_attacks(sq, defender) & self.bitboard(by, PieceType::Pawn))
+            | (KNIGHT_ATTACKS[sq] & self.bitboard(by, PieceType::Knight))
+            | (KING_ATTACKS[sq] & self.bitboard(by, PieceType::King))
+            | (sliding_attacks(sq, occupied, &BISHOP_DIRS)
+                & (self.bitboard(by, PieceType::Bishop) | queens))
+            | (sliding_attacks(sq, occupied, &ROOK_DIRS)
+                & (self.bitboard(by, PieceType::Rook) | queens))
+    }
+
+    /// Squares of `by`'s pieces attacking `sq` in the current position.
+    pub fn attackers_to(&self, sq: usize, by: Color) -> Vec<usize> {
+        BitIter(self.attackers_bitboard(sq, by, self.occupied())).collect()
+    }
+
+    /// Squares attacked by `by`'s pieces, including those holding `by`'s own
+    /// pieces, which are defended. Pawns attack diagonally forward, not the
+    /// squares they push to. Sliders stop at the first piece in the way, so
+    /// the square behind a king on a checking line isn't included.
+    pub fn attacked_squares(&self, by: Color) -> Vec<Square> {
+        let occupied = self.occupied();
+        let mut attacked = 0;
+        for piece_type in PieceType::ALL {
+            let piece = Piece {
+                piece_type,
+                color: by,
+            };
+            for sq in BitIter(self.bitboard(by, piece_type)) {
+                attacked |= attacks_from(piece, sq, occupied);
+            }
+        }
+        BitIter(attacked).filter_map(Square::from_index).collect()
+    }
+
+    /// Whether `m` is a legal move, checked on its own rather than by
+    /// generating every move: the side to move has a piece on the origin
+    /// that can reach the target, a promotion piece is given exactly when a
+    /// pawn reaches the last rank, and the move doesn't leave the king in
+    /// check. A side without a king has no legal moves, as in
+    /// `generate_moves`.
+    pub fn is_legal(&self, m: &Move) -> bool {
+        self.move_legality(m) == MoveLegality::Legal
+    }
+
+    /// Like `is_legal`, but says why an illegal move is rejected. Where the
+    /// piece can go is checked before king safety, so a blocked move reports
+    /// the blocker even if it would also leave the king in check.
+    pub fn move_legality(&self, m: &Move) -> MoveLegality {
+        if m.from_row >= 8 || m.from_col >= 8 || m.to_row >= 8 || m.to_col >= 8 {
+            return MoveLegality::NotAPseudoLegalTarget;
+        }
+        let us = self.turn;
+        let from = m.from_square().index();
+        let to = m.to_square().index();
+        let Some(piece) = self.piece_on(from) else {
+            return MoveLegality::NoPieceOnSource;
+        };
+        if piece.color != us {
+            return MoveLegality::WrongColor;
+        }
+
+        if self.is_castling(m) {
+            let side = if m.to_col > m.from_col {
+                KINGSIDE
+            } else {
+                QUEENSIDE
+            };
+            let allowed = m.promotion.is_none()
+                && self.castling_rooks[us.index()][side] == Some(to)
+                && can_castle(self, from, to, side);
+            return if allowed {
+                MoveLegality::Legal
+            } else {
+                MoveLegality::CannotCastle
+            };
+        }
+
+        let own = self.occupancy(us);
+        if own & (1u64 << to) != 0 {
+            return MoveLegality::OccupiedByOwnPiece;
+        }
+        let occupied = self.occupied();
+        let targets = if piece.piece_type == PieceType::Pawn {
+            let one = from as i32 + us.forward_dir() * 8;
+            let two = one + us.forward_dir() * 8;
+            let start_row = (home_row(us) as i32 + us.forward_dir()) as usize;
+            let is_empty = |sq: i32| (0..64).contains(&sq) && occupied & (1u64 << sq) == 0;
+            let mut targets = pawn_attacks(from, us) & self.occupancy(us.opponent());
+            if self.is_en_passant(m) {
+                targets |= pawn_attacks(from, us) & (1u64 << to);
+            }
+            if is_empty(one) {
+                targets |= 1u64 << one;
+                if from / 8 == start_row && is_empty(two) {
+                    targets |= 1u64 << two;
+                }
+            }
+            targets
+        } else {
+            attacks_from(piece, from, occupied)
+        };
+        if targets & (1u64 << to) == 0 {
+            // Where the piece could go with nothing else on the board
+            let unobstructed = if piece.piece_type == PieceType::Pawn {
+                let one = from as i32 + us.forward_dir() * 8;
+                let start_row = (home_row(us) as i32 + us.forward_dir()) as usize;
+                let mut pushes = 0;
+                if (0..64).contains(&one) {
+                    pushes |= 1u64 << one;
+                    if from / 8 == start_row {
+                        pushes |= 1u64 << (one + us.forward_dir() * 8);
+                    }
+                }
+                pushes
+            } else {
+                attacks_from(piece, from, 0)
+            };
+            return if unobstructed & (1u64 << to) != 0 {
+                MoveLegality::BlockedPath
+            } else {
+                MoveLegality::NotAPseudoLegalTarget
+            };
+        }
+
+        let promotes = piece.piece_type == PieceType::Pawn && m.to_row == home_row(us.opponent());
+        let valid_promotion = match m.promotion {
+            Some(pt) => promotes && PROMOTION_PIECES.contains(&pt),
+            None => !promotes,
+        };
+        if !valid_promotion {
+            return MoveLegality::InvalidPromotion;
+        }
+
+        // A side without a king has no legal moves
+        let mut b_clone = self.clone();
+        b_clone.make_move(m);
+        if self.bitboard(us, PieceType::King) == 0 || is_in_check(&b_clone, us) {
+            MoveLegality::LeavesKingInCheck
+        } else {
+            MoveLegality::Legal
+        }
+    }
+
+    /// Whether playing `m` leaves the opponent's king attacked.
+    pub fn gives_check(&self, m: &Move) -> bool {
+        let mut b_clone = self.clone();
+        b_clone.make_move(m);
+        is_in_check(&b_clone, b_clone.turn)
+    }
+
+    /// Number of pieces of each type per side.
+    pub fn piece_counts(&self) -> PieceCounts {
+        let mut counts = PieceCounts {
+            white: [0; 6],
+            black: [0; 6],
+        };
+        for piece_type in PieceType::ALL {
+            counts.white[piece_type.index()] = self.bitboard(Color::White, piece_type).count_ones();
+            counts.black[piece_type.index()] = self.bitboard(Color::Black, piece_type).count_ones();
+        }
+        counts
+    }
+
+    /// The piece counts of both sides, cheap to compare and match on.
+    pub fn material_signature(&self) -> MaterialSignature {
+        let mut counts = [[0; 6]; 2];
+        for color in [Color::White, Color::Black] {
+            for piece_type in PieceType::ALL {
+                counts[color.index()][piece_type.index()] =
+                    self.bitboard(color, piece_type).count_ones() as u8;
+            }
+        }
+        MaterialSignature { counts }
+    }
+
+    /// White material minus Black material in centipawns, using the same
+    /// piece values as the evaluation but no positional terms.
+    pub fn material_balance(&self) -> i32 {
+        self.material
+    }
+
+    pub fn status(&self) -> GameStatus {
+        let in_check = is_in_check(self, self.turn);
+        if generate_moves(self).is_empty() {
+            return if in_check {
+                GameStatus::Checkmate
+            } else {
+                GameStatus::Stalemate
+            };
+        }
+        if self.halfmove_clock >= 100 {
+            return GameStatus::FiftyMoveDraw;
+        }
+        if self.is_insufficient_material() {
+            return GameStatus::InsufficientMaterial;
+        }
+        if in_check {
+            GameStatus::InCheck
+        } else {
+            GameStatus::Ongoing
+        }
+    }
+
+    /// Like `status`, but also reports a threefold repetition given the
+    /// positions played before this one, oldest first.
+    pub fn status_with_history(&self, history: &[Board]) -> GameStatus {
+        let status = self.status();
+        let key = self.zobrist_key();
+        if !status.is_game_over() && history.iter().filter(|b| b.zobrist_key() == key).count() >= 2
+        {
+            return GameStatus::ThreefoldRepetition;
+        }
+        status
+    }
+
+    /// True for K vs K, K+minor vs K and bishops-only endings where every
+    /// bishop stands on the same square colour.
+    pub fn is_insufficient_material(&self) -> bool {
+        let signature = self.material_signature();
+        let sides = [Color::White, Color::Black];
+        let total = |pt: PieceType| sides.iter().map(|&c| signature.count(c, pt)).sum::<u8>();
+        if total(PieceType::Pawn) + total(PieceType::Rook) + total(PieceType::Queen) > 0 {
+            return false;
+        }
+        if total(PieceType::Knight) + total(PieceType::Bishop) <= 1 {
+            return true;
+        }
+        let bishops = self.bitboard(Color::White, PieceType::Bishop)
+            | self.bitboard(Color::Black, PieceType::Bishop);
+        total(PieceType::Knight) == 0
+            && (bishops & LIGHT_SQUARES == 0 || bishops & !LIGHT_SQUARES == 0)
+    }
+}
+
+/// A board square, stored as the internal index `row * 8 + col` where row 0
+/// is rank 8: a8 is 0 and h1 is 63.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Square(u8);
+
+impl Square {
+    pub fn from_index(index: usize) -> Option<Square> {
+        (index < 64).then_some(Square(index as u8))
+    }
+
+    pub fn from_row_col(row: usize, col: usize) -> Option<Square> {
+        (row < 8 && col < 8).then_some(Square((row * 8 + col) as u8))
+    }
+
+    /// Parses a square name such as `e4`.
+    pub fn from_algebraic(s: &str) -> Option<Square> {
+        match s.as_bytes() {
+            &[file @ b'a'..=b'h', rank @ b'1'..=b'8'] => {
+                Square::from_row_col((b'8' - rank) as usize, (file - b'a') as usize)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn to_algebraic(self) -> String {
+        format!("{}{}", (b'a' + self.file() as u8) as char, self.rank() + 1)
+    }
+
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    /// Board row, 0 being rank 8.
+    pub fn row(self) -> usize {
+        self.index() / 8
+    }
+
+    pub fn col(self) -> usize {
+        self.index() % 8
+    }
+
+    /// Rank from 0 (rank 1) to 7 (rank 8).
+    pub fn rank(self) -> usize {
+        7 - self.row()
+    }
+
+    /// File from 0 (a-file) to 7 (h-file).
+    pub fn file(self) -> usize {
+        self.col()
+    }
+}
+
+/// What a move does on the board it is played on, from `Board::classify`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MoveKind {
+    Quiet,
+    Capture,
+    EnPassant,
+    Castle,
+    /// A pawn reaching the last rank, whether or not it captures there.
+    Promotion {
+        capture: bool,
+    },
+    DoublePawnPush,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Move {
+    pub from_row: usize,
+    pub from_col: usize,
+    pub to_row: usize,
+    pub to_col: usize,
+    /// Piece a pawn reaching the last rank becomes. A pawn move onto the
+    /// last rank without one promotes to a queen.
+    #[serde(default)]
+    pub promotion: Option<PieceType>,
+}
+
+impl Move {
+    #[allow(dead_code, clippy::inherent_to_string)]
+    fn to_string(&self) -> String {
+        format!(
+            "{},{},{},{}",
+            self.from_row, self.from_col, self.to_row, self.to_col
+        )
+    }
+
+    pub fn new(from: Square, to: Square) -> Move {
+        Move {
+            from_row: from.row(),
+            from_col: from.col(),
+            to_row: to.row(),
+            to_col: to.col(),
+            promotion: None,
+        }
+    }
+
+    pub fn from_square(&self) -> Square {
+        Square((self.from_row * 8 + self.from_col) as u8)
+    }
+
+    pub fn to_square(&self) -> Square {
+        Square((self.to_row * 8 + self.to_col) as u8)
+    }
+
+    /// Coordinate notation such as `e2e4`, or `e7e8n` for a promotion;
+    /// castling is the king moving onto its rook.
+    pub fn to_coords(&self) -> String {
+        let promotion = self.promotion.map_or(String::new(), |p| p.to_string());
+        format!(
+            "{}{}{}",
+            self.from_square().to_algebraic(),
+            self.to_square().to_algebraic(),
+            promotion
+        )
+    }
+
+    /// Parses coordinate notation such as `e2e4`, with an optional `q`, `r`,
+    /// `b` or `n` suffix choosing a promotion piece.
+    pub fn from_coords(s: &str) -> Option<Move> {
+        if !(4..=5).contains(&s.len()) || !s.is_ascii() {
+            return None;
+        }
+        let mut m = Move::new(
+            Square::from_algebraic(&s[0..2])?,
+            Square::from_algebraic(&s[2..4])?,
+        );
+        if let Some(c) = s[4..].chars().next() {
+            let promotion = PieceType::from_char(c)
+                .filter(|p| c.is_ascii_lowercase() && PROMOTION_PIECES.contains(p))?;
+            m.promotion = Some(promotion);
+        }
+        Some(m)
+    }
+}
+
+impl Board {
+    /// The legal move written as `san` in standard algebraic notation, such
+    /// as `Nbd7`, `exd5`, `e8=Q+` or `O-O`. Returns `None` if no legal move
+    /// matches or the notation is ambiguous.
+    pub fn parse_san(&self, san: &str) -> Option<Move> {
+        let san = san.trim_end_matches(['+', '#', '!', '?']);
+        let legal_moves = generate_moves(self);
+        let castling_side = match san {
+            "O-O" | "0-0" => Some(KINGSIDE),
+            "O-O-O" | "0-0-0" => Some(QUEENSIDE),
+            _ => None,
+        };
+        if let Some(side) = castling_side {
+            return legal_moves
+                .into_iter()
+                .find(|m| self.is_castling(m) && (m.to_col > m.from_col) == (side == KINGSIDE));
+        }
+
+        let (piece_type, rest) = match san.chars().next()? {
+            'N' => (PieceType::Knight, &san[1..]),
+            'B' => (PieceType::Bishop, &san[1..]),
+            'R' => (PieceType::Rook, &san[1..]),
+            'Q' => (PieceType::Queen, &san[1..]),
+            'K' => (PieceType::King, &san[1..]),
+            _ => (PieceType::Pawn, san),
+        };
+        let (rest, promotion) = match rest.split_once('=') {
+            Some((rest, piece)) => (rest, Some(piece)),
+            None => (rest, None),
+        };
+        let promotion = match promotion {
+            None => None,
+            Some("Q") => Some(PieceType::Queen),
+            Some("R") => Some(PieceType::Rook),
+            Some("B") => Some(PieceType::Bishop),
+            Some("N") => Some(PieceType::Knight),
+            Some(_) => return None,
+        };
+        let rest = rest.replace('x', "");
+        if rest.len() < 2 || !rest.is_ascii() {
+            return None;
+        }
+        let (from_hint, to) = rest.split_at(rest.len() - 2);
+        let to = Square::from_algebraic(to)?;
+        let mut from_file = None;
+        let mut from_rank = None;
+        for c in from_hint.chars() {
+            match c {
+                'a'..='h' => from_file = Some((c as u8 - b'a') as usize),
+                '1'..='8' => from_rank = Some(8 - (c as u8 - b'0') as usize),
+                _ => return None,
+            }
+        }
+
+        let mut matches = legal_moves.into_iter().filter(|m| {
+            m.to_square() == to
+                && m.promotion == promotion
+                && !self.is_castling(m)
+                && from_file.is_none_or(|col| m.from_col == col)
+                && from_rank.is_none_or(|row| m.from_row == row)
+                && self
+                    .get_piece(m.from_row, m.from_col)
+                    .is_some_and(|p| p.piece_type == piece_type)
+        });
+        let m = matches.next()?;
+        matches.next().is_none().then_some(m)
+    }
+
+    /// The legal move `m` in standard algebraic notation, with the origin
+    /// file or rank given only where another piece of the same type could
+    /// also reach the target, and a `+` or `#` suffix for check and mate.
+    pub fn to_san(&self, m: &Move) -> String {
+        let mut san = if self.is_castling(m) {
+            if m.to_col > m.from_col {
+                "O-O".to_string()
+            } else {
+                "O-O-O".to_string()
+            }
+        } else {
+            let piece_type = self
+                .get_piece(m.from_row, m.from_col)
+                .map_or(PieceType::Pawn, |p| p.piece_type);
+            let capture = self.captured_piece(m).is_some();
+            let from = m.from_square().to_algebraic();
+            let mut san = String::new();
+            if piece_type == PieceType::Pawn {
+                if capture {
+                    san.push_str(&from[..1]);
+                }
+            } else {
+                san.push(piece_type.to_char().to_ascii_uppercase());
+                let rivals: Vec<Move> = generate_moves(self)
+                    .into_iter()
+                    .filter(|l| {
+                        l.to_square() == m.to_square()
+                            && l.from_square() != m.from_square()
+                            && !self.is_castling(l)
+                            && self
+                                .get_piece(l.from_row, l.from_col)
+                                .is_some_and(|p| p.piece_type == piece_type)
+                    })
+                    .collect();
+                if !rivals.is_empty() {
+                    if rivals.iter().all(|l| l.from_col != m.from_col) {
+                        san.push_str(&from[..1]);
+                    } else if rivals.iter().all(|l| l.from_row != m.from_row) {
+                        san.push_str(&from[1..]);
+                    } else {
+                        san.push_str(&from);
+                    }
+                }
+            }
+            if capture {
+                san.push('x');
+            }
+            san.push_str(&m.to_square().to_algebraic());
+            if let Some(promotion) = m.promotion {
+                san.push('=');
+                san.push(promotion.to_char().to_ascii_uppercase());
+            }
+            san
+        };
+
+        let mut after = self.clone();
+        after.make_move(m);
+        if is_in_check(&after, after.turn) {
+            san.push(if generate_moves(&after).is_empty() {
+                '#'
+            } else {
+                '+'
+            });
+        }
+        san
+    }
+}
+
+/// Why a PGN game can't be read.
+#[derive(Clone, PartialEq, Debug)]
+pub enum PgnError {
+    /// A tag pair that isn't of the form `[Name "value"]`.
+    InvalidTag(String),
+    InvalidFen(PositionError),
+    /// A `{` comment or `(` variation that is never closed.
+    Unclosed(char),
+    /// A move that isn't legal, or isn't valid SAN, with its ply counted
+    /// from 1.
+    IllegalMove {
+        ply: usize,
+        san: String,
+    },
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PgnError::InvalidTag(line) => write!(f, "invalid tag pair {}", line),
+            PgnError::InvalidFen(e) => write!(f, "invalid FEN tag: {}", e),
+            PgnError::Unclosed(c) => write!(f, "unclosed '{}'", c),
+            PgnError::IllegalMove { ply, san } => {
+                write!(f, "illegal move '{}' at ply {}", san, ply)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PgnError {}
+
+/// A game read from PGN.
+#[derive(Clone)]
+pub struct Game {
+    /// Tag pairs in the order given, such as `("White", "Carlsen")`.
+    pub tags: Vec<(String, String)>,
+    /// Position before the first move: the `FEN` tag, or the standard start.
+    pub start: Board,
+    pub moves: Vec<Move>,
+    /// Position after the last move.
+    pub board: Board,
+    /// Opening book for `book_move`, loaded by `set_book_from_file`.
+    pub book: Option<PolyglotBook>,
+}
+
+impl Game {
+    /// Reads the tag pairs and movetext of a single PGN game. Comments,
+    /// NAGs, move numbers and the result are skipped, and so are
+    /// variations, so only the main line is played.
+    pub fn from_pgn(pgn: &str) -> Result<Game, PgnError> {
+        let mut tags = Vec::new();
+        let mut lines = pgn.lines().map(str::trim).peekable();
+        while let Some(line) = lines.next_if(|l| l.is_empty() || l.starts_with('[')) {
+            if line.is_empty() {
+                continue;
+            }
+            let tag = line
+                .strip_prefix('[')
+                .and_then(|l| l.strip_suffix(']'))
+                .and_then(|l| l.split_once(char::is_whitespace))
+                .and_then(|(name, value)| {
+                    let value = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+                    Some((name.to_string(), value.replace("\\\"", "\"")))
+                })
+                .ok_or_else(|| PgnError::InvalidTag(line.to_string()))?;
+            tags.push(tag);
+        }
+
+        let start = match tags.iter().find(|(name, _)| name == "FEN") {
+            Some((_, fen)) => Board::try_from_fen(fen).map_err(PgnError::InvalidFen)?,
+            None => Board::from_fen(START_FEN),
+        };
+        let mut game = Game {
+            tags,
+            board: start.clone(),
+            start,
+            moves: Vec::new(),
+            book: None,
+        };
+        let movetext: Vec<&str> = lines.collect();
+        for token in pgn_tokens(&movetext.join("\n"))? {
+            let san = match token.rfind('.') {
+                // Move numbers may be written against the move, as in `1.e4`
+                Some(dot) if token.starts_with(|c: char| c.is_ascii_digit()) => &token[dot + 1..],
+                _ => token,
+            };
+            if san.is_empty() || matches!(san, "1-0" | "0-1" | "1/2-1/2" | "*") {
+                continue;
+            }
+            let m = game
+                .board
+                .parse_san(san)
+                .ok_or_else(|| PgnError::IllegalMove {
+                    ply: game.moves.len() + 1,
+                    san: san.to_string(),
+                })?;
+            game.board.make_move(&m);
+            game.moves.push(m);
+        }
+        Ok(game)
+    }
+
+    /// Takes back the last move, replaying the rest from `start` so the
+    /// board comes back exactly as it was. Returns the move taken back, or
+    /// `None` at the start of the game.
+    pub fn undo(&mut self) -> Option<Move> {
+        let m = self.moves.pop()?;
+        let mut board = self.start.clone();
+        for played in &self.moves {
+            board.make_move(played);
+        }
+        self.board = board;
+        Some(m)
+    }
+
+    /// Writes the game as PGN: the tag pairs, then the moves in SAN with
+    /// move numbers, ending with the `Result` tag's value or `*`.
+    pub fn to_pgn(&self) -> String {
+        let mut pgn = String::new();
+        for (name, value) in &self.tags {
+            pgn.push_str(&format!("[{} \"{}\"]\n", name, value.replace('"', "\\\"")));
+        }
+        if !self.tags.is_empty() {
+            pgn.push('\n');
+        }
+
+        let mut tokens = Vec::new();
+        let mut board = self.start.clone();
+        let mut number = 1;
+        for (i, m) in self.moves.iter().enumerate() {
+            match board.turn {
+                Color::White => tokens.push(format!("{}.", number)),
+                Color::Black if i == 0 => tokens.push(format!("{}...", number)),
+                Color::Black => {}
+            }
+            tokens.push(board.to_san(m));
+            if board.turn == Color::Black {
+                number += 1;
+            }
+            board.make_move(m);
+        }
+        let result = self
+            .tags
+            .iter()
+            .find(|(name, _)| name == "Result")
+            .map_or("*", |(_, value)| value.as_str());
+        tokens.push(result.to_string());
+        pgn.push_str(&tokens.join(" "));
+        pgn.push('\n');
+        pgn
+    }
+}
+
+/// Splits PGN movetext into whitespace-separated tokens, dropping `{...}`
+/// and `;` comments, `(...)` variations and `$n` NAGs.
+fn pgn_tokens(movetext: &str) -> Result<Vec<&str>, PgnError> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    let mut chars = movetext.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let ends_token = c.is_whitespace() || matches!(c, '{' | ';' | '(' | '$');
+        if ends_token {
+            if let Some(s) = start.take() {
+                tokens.push(&movetext[s..i]);
+            }
+        }
+        match c {
+            '{' => {
+                chars
+                    .find(|&(_, c)| c == '}')
+                    .ok_or(PgnError::Unclosed('{'))?;
+            }
+            ';' => {
+                chars.find(|&(_, c)| c == '\n');
+            }
+            '(' => {
+                let mut depth = 1;
+                while depth > 0 {
+                    match chars.next().ok_or(PgnError::Unclosed('('))?.1 {
+                        '(' => depth += 1,
+                        ')' => depth -= 1,
+                        // A comment in a variation may hold a parenthesis
+                        '{' => {
+                            chars
+                                .find(|&(_, c)| c == '}')
+                                .ok_or(PgnError::Unclosed('{'))?;
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            '$' => while chars.next_if(|&(_, c)| c.is_ascii_digit()).is_some() {},
+            _ if ends_token => {}
+            _ => {
+                start.get_or_insert(i);
+            }
+        }
+    }
+    if let Some(s) = start {
+        tokens.push(&movetext[s..]);
+    }
+    Ok(tokens)
+}
+
+// Opening book: mainlines in coordinate notation, replayed from the start
+// position. A position reached by any prefix of a line is answered with the
+// line's next move.
+const BOOK_LINES: &[&str] = &[
+    "e2e4 e7e5 g1f3 b8c6 f1b5 a7a6 b5a4 g8f6",
+    "e2e4 e7e5 g1f3 b8c6 f1c4 f8c5 c2c3 g8f6",
+    "e2e4 e7e5 g1f3 b8c6 d2d4 e5d4 f3d4 g8f6",
+    "e2e4 c7c5 g1f3 d7d6 d2d4 c5d4 f3d4 g8f6",
+    "e2e4 c7c5 g1f3 b8c6 d2d4 c5d4 f3d4 g8f6",
+    "e2e4 e7e6 d2d4 d7d5 b1c3 g8f6",
+    "e2e4 c7c6 d2d4 d7d5 b1c3 d5e4 c3e4 c8f5",
+    "d2d4 d7d5 c2c4 e7e6 b1c3 g8f6 c1g5 f8e7",
+    "d2d4 d7d5 c2c4 c7c6 g1f3 g8f6",
+    "d2d4 g8f6 c2c4 e7e6 g1f3 d7d5",
+    "d2d4 g8f6 c2c4 g7g6 b1c3 f8g7 e2e4 d7d6",
+    "c2c4 e7e5 b1c3 g8f6 g1f3 b8c6",
+    "g1f3 d7d5 d2d4 g8f6 c2c4 e7e6",
+];
+
+/// Book replies for `board`, without duplicates.
+pub fn book_moves(board: &Board) -> Vec<Move> {
+    book_moves_in(BOOK_LINES, board)
+}
+
+/// Replies for `board` from `lines`, which are given like the built-in
+/// book: moves in coordinate notation from the start position.
+pub fn book_moves_in(lines: &[&str], board: &Board) -> Vec<Move> {
+    let key = board.zobrist_key();
+    let mut moves: Vec<Move> = Vec::new();
+    for line in lines {
+        let mut b = Board::from_fen(START_FEN);
+        for m in line.split_whitespace().filter_map(Move::from_coords) {
+            if b.zobrist_key() == key {
+                if !moves.contains(&m) {
+                    moves.push(m);
+                }
+                break;
+            }
+            b.make_move(&m);
+        }
+    }
+    moves
+}
+
+/// Pseudo-random number for picking among equally good book moves. The
+/// clock isn't available on wasm32, where calls just advance a counter.
+fn random_u64() -> u64 {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    #[allow(unused_mut)]
+    let mut seed = COUNTER.fetch_add(1, Ordering::Relaxed);
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        seed ^= std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos() as u64);
+    }
+    splitmix64(seed).1
+}
+
+// Evaluation
+const PAWN_VAL: i32 = 100;
+const KNIGHT_VAL: i32 = 320;
+const BISHOP_VAL: i32 = 330;
+const ROOK_VAL: i32 = 500;
+const QUEEN_VAL: i32 = 900;
+const KING_VAL: i32 = 20000;
+
+const PIECE_VALUES: [i32; 6] = [
+    PAWN_VAL, KNIGHT_VAL, BISHOP_VAL, ROOK_VAL, QUEEN_VAL, KING_VAL,
+];
+
+/// Weights used by the static evaluation. `Default` is the engine's normal
+/// play; the presets shift it towards a style.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EvalParams {
+    /// Centipawn value of each piece type, in `PieceType::ALL` order.
+    pub piece_values: [i32; 6],
+    pub bishop_pair_bonus: i32,
+    /// Per knight, per pawn above (or below) five of the knight's own side.
+    pub knight_pawn_adjust: i32,
+    /// Per square a knight, bishop, rook or queen attacks that isn't
+    /// occupied by its own side.
+    pub mobility_weight: i32,
+    /// Per pawn beyond the first on a file.
+    pub doubled_pawn_penalty: i32,
+    /// Per pawn with no friendly pawn on either neighbouring file.
+    pub isolated_pawn_penalty: i32,
+    /// Percentage applied to the king safety term.
+    pub king_safety_weight: i32,
+    /// Per rook on a file with no pawns.
+    pub rook_open_file_bonus: i32,
+    /// Per rook on a file with only enemy pawns.
+    pub rook_semi_open_file_bonus: i32,
+    /// Per rook on the seventh rank from its own side.
+    pub rook_seventh_rank_bonus: i32,
+    /// Per square the side ahead in material brings its king closer to the
+    /// other king, in full once only pawns are left and fading out as
+    /// pieces come back on.
+    pub king_proximity_bonus: i32,
+}
+
+impl Default for EvalParams {
+    fn default() -> Self {
+        EvalParams {
+            piece_values: PIECE_VALUES,
+            bishop_pair_bonus: 30,
+            knight_pawn_adjust: 5,
+            mobility_weight: 0,
+            doubled_pawn_penalty: 10,
+            isolated_pawn_penalty: 10,
+            king_safety_weight: 100,
+            rook_open_file_bonus: 20,
+            rook_semi_open_file_bonus: 10,
+            rook_seventh_rank_bonus: 20,
+            king_proximity_bonus: 6,
+        }
+    }
+}
+
+impl EvalParams {
+    /// Values open lines and pressure on the enemy king over structure.
+    pub fn aggressive() -> Self {
+        EvalParams {
+            mobility_weight: 4,
+            king_safety_weight: 200,
+            ..EvalParams::default()
+        }
+    }
+
+    /// Values sound pawns, the bishop pair and piece activity.
+    pub fn positional() -> Self {
+        EvalParams {
+            bishop_pair_bonus: 50,
+            mobility_weight: 2,
+            doubled_pawn_penalty: 20,
+            isolated_pawn_penalty: 15,
+            ..EvalParams::default()
+        }
+    }
+}
+
+/// The terms of the static evaluation, each in centipawns from White's
+/// point of view. `total` is what `evaluate` returns. There is no
+/// piece-square term because the evaluation has no piece-square tables:
+/// where pieces stand is scored through mobility, king safety and rook
+/// placement instead.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct EvalBreakdown {
+    pub material: i32,
+    pub bishop_pair: i32,
+    /// Knights gaining value with many pawns on the board and losing it as
+    /// pawns come off.
+    pub knight_pawns: i32,
+    pub mobility: i32,
+    pub pawn_structure: i32,
+    pub king_safety: i32,
+    pub rook_placement: i32,
+    pub king_proximity: i32,
+    /// Driving a bare king to the edge in basic mates.
+    pub basic_mate: i32,
+}
+
+impl EvalBreakdown {
+    pub fn total(&self) -> i32 {
+        self.material
+            + self.bishop_pair
+            + self.knight_pawns
+            + self.mobility
+            + self.pawn_structure
+            + self.king_safety
+            + self.rook_placement
+            + self.king_proximity
+            + self.basic_mate
+    }
+}
+
+/// Static evaluation from White's point of view.
+pub fn evaluate(board: &Board, params: &EvalParams) -> i32 {
+    evaluate_verbose(board, params).total()
+}
+
+/// Static evaluation split into its terms, for seeing what drives a score.
+pub fn evaluate_verbose(board: &Board, params: &EvalParams) -> EvalBreakdown {
+    evaluate_terms(board, params, pawn_score(board, params))
+}
+
+/// The evaluation terms given the pawn structure score, which the search
+/// may have looked up in its pawn hash table rather than computed.
+fn evaluate_terms(board: &Board, params: &EvalParams, pawn_structure: i32) -> EvalBreakdown {
+    // The board keeps the material balance for the default piece values
+    let material = if params.piece_values == PIECE_VALUES {
+        board.material_balance()
+    } else {
+        let counts = board.piece_counts();
+        PieceType::ALL
+            .iter()
+            .map(|&pt| {
+                params.piece_values[pt.index()]
+                    * (counts.get(Color::White, pt) as i32 - counts.get(Color::Black, pt) as i32)
+            })
+            .sum()
+    };
+    let bishop_pair = |color| {
+        if board.bitboard(color, PieceType::Bishop).count_ones() >= 2 {
+            params.bishop_pair_bonus
+        } else {
+            0
+        }
+    };
+    EvalBreakdown {
+        material,
+        bishop_pair: bishop_pair(Color::White) - bishop_pair(Color::Black),
+        knight_pawns: knight_pawns(board, Color::White, params)
+            - knight_pawns(board, Color::Black, params),
+        mobility: if params.mobility_weight != 0 {
+            params.mobility_weight * (mobility(board, Color::White) - mobility(board, Color::Black))
+        } else {
+            0
+        },
+        pawn_structure,
+        king_safety: (king_safety(board, Color::White) - king_safety(board, Color::Black))
+            * params.king_safety_weight
+            / 100,
+        rook_placement: rook_placement(board, Color::White, params)
+            - rook_placement(board, Color::Black, params),
+        king_proximity: king_proximity(board, params),
+        basic_mate: basic_mate(board),
+    }
+}
+
+/// Knights gaining value in closed positions with many pawns and losing it
+/// as pawns come off.
+fn knight_pawns(board: &Board, color: Color, params: &EvalParams) -> i32 {
+    let knights = board.bitboard(color, PieceType::Knight).count_ones() as i32;
+    let pawns = board.bitboard(color, PieceType::Pawn).count_ones() as i32;
+    knights * (pawns - 5) * params.knight_pawn_adjust
+}
+
+/// Squares attacked by `color`'s knights, bishops, rooks and queens that
+/// aren't occupied by its own pieces.
+fn mobility(board: &Board, color: Color) -> i32 {
+    let own = board.occupancy(color);
+    let occupied = board.occupied();
+    let mut squares = 0;
+    for piece_type in [
+        PieceType::Knight,
+        PieceType::Bishop,
+        PieceType::Rook,
+        PieceType::Queen,
+    ] {
+        let piece = Piece { piece_type, color };
+        for sq in BitIter(board.bitboard(color, piece_type)) {
+            squares += (attacks_from(piece, sq, occupied) & !own).count_ones() as i32;
+        }
+    }
+    squares
+}
+
+/// Bonuses for `color`'s rooks on open and semi-open files and on the
+/// seventh rank.
+fn rook_placement(board: &Board, color: Color, params: &EvalParams) -> i32 {
+    let own_pawns = board.bitboard(color, PieceType::Pawn);
+    let enemy_pawns = board.bitboard(color.opponent(), PieceType::Pawn);
+    let seventh_row = (home_row(color.opponent()) as i32 - color.forward_dir()) as usize;
+    let mut score = 0;
+    for sq in BitIter(board.bitboard(color, PieceType::Rook)) {
+        let file = file_mask(sq % 8);
+        if own_pawns & file == 0 {
+            score += if enemy_pawns & file == 0 {
+                params.rook_open_file_bonus
+            } else {
+                params.rook_semi_open_file_bonus
+            };
+        }
+        if sq / 8 == seventh_row {
+            score += params.rook_seventh_rank_bonus;
+        }
+    }
+    score
+}
+
+/// The pawn structure term from White's point of view. It depends on
+/// nothing but the pawns, so `PawnHashTable` can cache it.
+fn pawn_score(board: &Board, params: &EvalParams) -> i32 {
+    if params.doubled_pawn_penalty != 0 || params.isolated_pawn_penalty != 0 {
+        pawn_structure(board, Color::White, params) - pawn_structure(board, Color::Black, params)
+    } else {
+        0
+    }
+}
+
+/// Slots in the search's pawn hash table. Pawn skeletons repeat far more
+/// than whole positions, so a small table catches most of them.
+const PAWN_HASH_SLOTS: usize = 1 << 14;
+
+/// Pawn structure scores by pawn key, one slot per key. The scores are
+/// only good for the penalties they were computed with, so the table
+/// empties itself when asked about others.
+struct PawnHashTable {
+    entries: Vec<(u64, i32)>,
+    penalties: (i32, i32),
+}
+
+impl PawnHashTable {
+    fn new() -> Self {
+        // An empty slot reads as the pawnless position, whose key and score
+        // are both zero
+        PawnHashTable {
+            entries: vec![(0, 0); PAWN_HASH_SLOTS],
+            penalties: (0, 0),
+        }
+    }
+
+    /// `pawn_score(board, params)`, from the table when the same pawns
+    /// were scored before.
+    fn score(&mut self, board: &Board, params: &EvalParams) -> i32 {
+        let penalties = (params.doubled_pawn_penalty, params.isolated_pawn_penalty);
+        if penalties == (0, 0) {
+            return 0;
+        }
+        if penalties != self.penalties {
+            self.entries.fill((0, 0));
+            self.penalties = penalties;
+        }
+        let key = board.pawn_key;
+        let slot = key as usize & (PAWN_HASH_SLOTS - 1);
+        match self.entries[slot] {
+            (k, score) if k == key => score,
+            _ => {
+                let score = pawn_score(board, params);
+                self.entries[slot] = (key, score);
+                score
+            }
+        }
+    }
+}
+
+/// Doubled and isolated pawn penalties, as a negative score.
+fn pawn_structure(board: &Board, color: Color, params: &EvalParams) -> i32 {
+    let pawns = board.bitboard(color, PieceType::Pawn);
+    let mut score = 0;
+    for col in 0..8 {
+        let on_file = (pawns & file_mask(col)).count_ones() as i32;
+        if on_file == 0 {
+            continue;
+        }
+        score -= (on_file - 1) * params.doubled_pawn_penalty;
+        let neighbours = (col.saturating_sub(1)..=(col + 1).min(7))
+            .filter(|&c| c != col)
+            .fold(0, |mask, c| mask | file_mask(c));
+        if pawns & neighbours == 0 {
+            score -= on_file * params.isolated_pawn_penalty;
+        }
+    }
+    score
+}
+
+// Basic mates: squares between each square and the nearest edge
+#[rustfmt::skip]
+const DISTANCE_TO_EDGE: [i32; 64] = [
+    0, 0, 0, 0, 0, 0, 0, 0,
+    0, 1, 1, 1, 1, 1, 1, 0,
+    0, 1, 2, 2, 2, 2, 1, 0,
+    0, 1, 2, 3, 3, 2, 1, 0,
+    0, 1, 2, 3, 3, 2, 1, 0,
+    0, 1, 2, 2, 2, 2, 1, 0,
+    0, 1, 1, 1, 1, 1, 1, 0,
+    0, 0, 0, 0, 0, 0, 0, 0,
+];
+const EDGE_DRIVE_BONUS: i32 = 30;
+const KING_PROXIMITY_BONUS: i32 = 10;
+
+/// For king and queen or king and rook against a bare king, a bonus for
+/// pushing the bare king to the edge and bringing the other king up to help
+/// mate it, which a shallow search can't see for itself. Zero for any
+/// other material, from White's point of view.
+fn basic_mate(board: &Board) -> i32 {
+    let signature = board.material_signature();
+    let (strong, weak) = if signature.is_bare_king(Color::Black) {
+        (Color::White, Color::Black)
+    } else if signature.is_bare_king(Color::White) {
+        (Color::Black, Color::White)
+    } else {
+        return 0;
+    };
+    let heavy =
+        signature.count(strong, PieceType::Queen) + signature.count(strong, PieceType::Rook);
+    let kings = signature.count(strong, PieceType::King) + signature.count(weak, PieceType::King);
+    if signature.pieces(strong) != 1 || heavy != 1 || kings != 2 {
+        return 0;
+    }
+    let weak_ksq = board.bitboard(weak, PieceType::King).trailing_zeros() as usize;
+    let strong_ksq = board.bitboard(strong, PieceType::King).trailing_zeros() as usize;
+    let king_distance =
+        (weak_ksq / 8).abs_diff(strong_ksq / 8) + (weak_ksq % 8).abs_diff(strong_ksq % 8);
+    let bonus = EDGE_DRIVE_BONUS * (3 - DISTANCE_TO_EDGE[weak_ksq])
+        + KING_PROXIMITY_BONUS * (14 - king_distance as i32);
+    match strong {
+        Color::White => bonus,
+        Color::Black => -bonus,
+    }
+}
+
+/// Non-pawn material, both sides together, at and above which the king
+/// proximity term is off.
+const ENDGAME_MATERIAL: i32 = 2600;
+
+/// Bonus for the side ahead in material keeping its king near the other
+/// king, which helps it build mating nets and escort pawns in endings
+/// rather than shuffle. Scaled by how little non-pawn material remains, so
+/// it's gone by the middlegame. From White's point of view.
+fn king_proximity(board: &Board, params: &EvalParams) -> i32 {
+    let phase = ENDGAME_MATERIAL - non_pawn_material(board);
+    let balance = board.material_balance();
+    if phase <= 0 || balance == 0 || params.king_proximity_bonus == 0 {
+        return 0;
+    }
+    let white_king = board.bitboard(Color::White, PieceType::King);
+    let black_king = board.bitboard(Color::Black, PieceType::King);
+    if white_king == 0 || black_king == 0 {
+        return 0;
+    }
+    let (w, b) = (
+        white_king.trailing_zeros() as usize,
+        black_king.trailing_zeros() as usize,
+    );
+    let distance = (w / 8).abs_diff(b / 8).max((w % 8).abs_diff(b % 8)) as i32;
+    let bonus = params.king_proximity_bonus * (7 - distance) * phase / ENDGAME_MATERIAL;
+    if balance > 0 {
+        bonus
+    } else {
+        -bonus
+    }
+}
+
+// King safety
+const SHIELD_CLOSE_BONUS: i32 = 10;
+const SHIELD_FAR_BONUS: i32 = 5;
+const HALF_OPEN_FILE_PENALTY: i32 = 15;
+const OPEN_FILE_PENALTY: i32 = 25;
+
+/// Pawn shield and open files in front of a king on its back rank, minus a
+/// penalty growing with the number and weight of enemy pieces attacking the
+/// king's surroundings.
+fn king_safety(board: &Board, color: Color) -> i32 {
+    let king = board.bitboard(color, PieceType::King);
+    if king == 0 {
+        return 0;
+    }
+    let ksq = king.trailing_zeros() as usize;
+    let (kr, kc) = (ksq / 8, ksq % 8);
+    let (enemy, home_row, forward) = match color {
+        Color::White => (Color::Black, 7, -1),
+        Color::Black => (Color::White, 0, 1),
+    };
+    let own_pawns = board.bitboard(color, PieceType::Pawn);
+    let enemy_pawns = board.bitboard(enemy, PieceType::Pawn);
+    let mut score = 0;
+
+    if kr == home_row {
+        let close_row = (home_row as i32 + forward) as usize;
+        let far_row = (home_row as i32 + 2 * forward) as usize;
+        for c in kc.saturating_sub(1)..=(kc + 1).min(7) {
+            if own_pawns & (1u64 << (close_row * 8 + c)) != 0 {
+                score += SHIELD_CLOSE_BONUS;
+            } else if own_pawns & (1u64 << (far_row * 8 + c)) != 0 {
+                score += SHIELD_FAR_BONUS;
+            }
+            if own_pawns & file_mask(c) == 0 {
+                score -= if enemy_pawns & file_mask(c) == 0 {
+                    OPEN_FILE_PENALTY
+                } else {
+                    HALF_OPEN_FILE_PENALTY
+                };
+            }
+        }
+    }
+
+    let zone = KING_ATTACKS[ksq] | king;
+    let occupied = board.occupied();
+    let mut attackers = 0;
+    let mut attack_weight = 0;
+    for (piece_type, weight) in [
+        (PieceType::Knight, 20),
+        (PieceType::Bishop, 20),
+        (PieceType::Rook, 40),
+        (PieceType::Queen, 80),
+    ] {
+        let piece = Piece {
+            piece_type,
+            color: enemy,
+        };
+        for sq in BitIter(board.bitboard(enemy, piece_type)) {
+            if attacks_from(piece, sq, occupied) & zone != 0 {
+                attackers += 1;
+                attack_weight += weight;
+            }
+        }
+    }
+    score - attack_weight * attackers / 4
+}
+
+fn push_moves(moves: &mut Vec<Move>, from: usize, targets: u64) {
+    for to in BitIter(targets) {
+        moves.push(Move {
+            from_row: from / 8,
+            from_col: from % 8,
+            to_row: to / 8,
+            to_col: to % 8,
+            promotion: None,
+        });
+    }
+}
+
+// Pieces a pawn may promote to, most valuable first
+const PROMOTION_PIECES: [PieceType; 4] = [
+    PieceType::Queen,
+    PieceType::Rook,
+    PieceType::Bishop,
+    PieceType::Knight,
+];
+
+/// Like `push_moves` for a pawn, adding one move per promotion piece for
+/// each target on the first or last rank.
+fn push_pawn_moves(moves: &mut Vec<Move>, from: usize, targets: u64) {
+    const BACK_RANKS: u64 = 0xFF | (0xFF << 56);
+    push_moves(moves, from, targets & !BACK_RANKS);
+    for to in BitIter(targets & BACK_RANKS) {
+        for piece_type in PROMOTION_PIECES {
+            moves.push(Move {
+                from_row: from / 8,
+                from_col: from % 8,
+                to_row: to / 8,
+                to_col: to % 8,
+                promotion: Some(piece_type),
+            });
+        }
+    }
+}
+
+// Move generation
+/// The legal moves of the side to move. Positions that `Board::validate`
+/// rejects for a missing king are still handled: the side without a king has
+/// no moves, and since a missing king counts as being in check, every move
+/// of the other side leaves the kingless side checkmated.
+pub fn generate_moves(board: &Board) -> Vec<Move> {
+    generate(board, false)
+}
+
+/// The legal moves that capture, including en passant and promotions that
+/// capture.
+pub fn generate_captures(board: &Board) -> Vec<Move> {
+    generate(board, true)
+}
+
+/// Legal moves, or only captures if `captures_only` is set.
+fn generate(board: &Board, captures_only: bool) -> Vec<Move> {
+    let mut moves = Vec::new();
+    let us = board.turn;
+    let them = us.opponent();
+    let own = board.occupancy(us);
+    let enemy = board.occupancy(them);
+    let occupied = own | enemy;
+    // Squares a non-pawn move may land on
+    let allowed = if captures_only { enemy } else { !own };
+
+    let Some(ksq) = BitIter(board.bitboard(us, PieceType::King)).next() else {
+        return moves; // No king, so nothing is legal
+    };
+
+    // In check, a move other than the king's has to capture a lone checker
+    // or block a sliding checker's ray; double check leaves only king moves
+    let checkers = board.checkers();
+    let evasions = match checkers.count_ones() {
+        0 => !0,
+        1 => {
+            let checker = checkers.trailing_zeros() as usize;
+            (1u64 << checker)
+                | between(ksq, checker, &ROOK_DIRS)
+                | between(ksq, checker, &BISHOP_DIRS)
+        }
+        _ => 0,
+    };
+
+    if evasions != 0 {
+        // Pawns
+        let dir = us.forward_dir() * 8;
+        let start_row = (home_row(us) as i32 + us.forward_dir()) as usize;
+        for from in BitIter(board.bitboard(us, PieceType::Pawn)) {
+            let mut targets = pawn_attacks(from, us) & enemy;
+            let is_empty = |sq: i32| (0..64).contains(&sq) && occupied & (1u64 << sq) == 0;
+            let one = from as i32 + dir;
+            let two = one + dir;
+            if is_empty(one) && !captures_only {
+                targets |= 1u64 << one;
+            }
+            // A double push needs the pawn on its home rank and both the square it
+            // crosses and the square it lands on to be empty
+            if from / 8 == start_row && is_empty(one) && is_empty(two) && !captures_only {
+                targets |= 1u64 << two;
+            }
+            push_pawn_moves(&mut moves, from, targets & evasions);
+        }
+
+        // En passant may also answer a check from the pawn it takes. Taking
+        // removes two pawns from the capturing side's rank, which can expose
+        // the king along it, so each capture is tried on a copy
+        if let Some(ep) = board.en_passant.map(|sq| sq.index()) {
+            let victim = (ep as i32 - dir) as usize;
+            if occupied & (1u64 << ep) == 0
+                && board.bitboard(them, PieceType::Pawn) & (1u64 << victim) != 0
+                && evasions & ((1u64 << ep) | (1u64 << victim)) != 0
+            {
+                for from in BitIter(pawn_attacks(ep, them) & board.bitboard(us, PieceType::Pawn)) {
+                    let m = Move {
+                        from_row: from / 8,
+                        from_col: from % 8,
+                        to_row: ep / 8,
+                        to_col: ep % 8,
+                        promotion: None,
+                    };
+                    let mut b_clone = board.clone();
+                    b_clone.make_move(&m);
+                    if !is_in_check(&b_clone, us) {
+                        moves.push(m);
+                    }
+                }
+            }
+        }
+
+        for from in BitIter(board.bitboard(us, PieceType::Knight)) {
+            push_moves(&mut moves, from, KNIGHT_ATTACKS[from] & allowed & evasions);
+        }
+        for from in BitIter(board.bitboard(us, PieceType::Bishop)) {
+            let attacks = sliding_attacks(from, occupied, &BISHOP_DIRS);
+            push_moves(&mut moves, from, attacks & allowed & evasions);
+        }
+        for from in BitIter(board.bitboard(us, PieceType::Rook)) {
+            let attacks = sliding_attacks(from, occupied, &ROOK_DIRS);
+            push_moves(&mut moves, from, attacks & allowed & evasions);
+        }
+        for from in BitIter(board.bitboard(us, PieceType::Queen)) {
+            let attacks = sliding_attacks(from, occupied, &BISHOP_DIRS)
+                | sliding_attacks(from, occupied, &ROOK_DIRS);
+            push_moves(&mut moves, from, attacks & allowed & evasions);
+        }
+    }
+
+    push_moves(&mut moves, ksq, KING_ATTACKS[ksq] & allowed);
+    if checkers == 0 && !captures_only {
+        for side in [KINGSIDE, QUEENSIDE] {
+            if let Some(rook_sq) = board.castling_rooks[us.index()][side] {
+                if can_castle(board, ksq, rook_sq, side) {
+                    push_moves(&mut moves, ksq, 1u64 << rook_sq);
+                }
+            }
+        }
+    }
+
+    // Only a pinned piece or the king can expose the king to a new attack,
+    // so every other move generated is already legal
+    let pinned = pinned_pieces(board, ksq, them);
+    moves.retain(|m| {
+        let from = m.from_square().index();
+        if from == ksq || pinned & (1u64 << from) != 0 {
+            let mut b_clone = board.clone();
+            b_clone.make_move(m);
+            !is_in_check(&b_clone, us)
+        } else {
+            true
+        }
+    });
+    moves
+}
+
+/// Squares strictly between `a` and `b` if they share a line along `dirs`.
+fn between(a: usize, b: usize, dirs: &[(i32, i32); 4]) -> u64 {
+    if sliding_attacks(a, 1u64 << b, dirs) & (1u64 << b) == 0 {
+        return 0;
+    }
+    sliding_attacks(a, 1u64 << b, dirs) & sliding_attacks(b, 1u64 << a, dirs)
+}
+
+/// Pieces of the side to move that are the only thing standing between their
+/// king on `ksq` and an enemy slider.
+fn pinned_pieces(board: &Board, ksq: usize, them: Color) -> u64 {
+    pins(board, ksq, them.opponent()).fold(0, |pinned, (sq, _)| pinned | 1u64 << sq)
+}
+
+/// Each of `us`'s pieces that is the only thing standing between its king
+/// on `ksq` and an enemy slider, paired with that slider's square.
+fn pins(board: &Board, ksq: usize, us: Color) -> impl Iterator<Item = (usize, usize)> + '_ {
+    let them = us.opponent();
+    let own = board.occupancy(us);
+    let enemy = board.occupancy(them);
+    let queens = board.bitboard(them, PieceType::Queen);
+    [
+        (&ROOK_DIRS, board.bitboard(them, PieceType::Rook) | queens),
+        (
+            &BISHOP_DIRS,
+            board.bitboard(them, PieceType::Bishop) | queens,
+        ),
+    ]
+    .into_iter()
+    .flat_map(move |(dirs, sliders)| {
+        // Looking through our own pieces finds every slider that could pin
+        BitIter(sliding_attacks(ksq, enemy, dirs) & sliders).filter_map(move |sniper| {
+            let blockers = between(ksq, sniper, dirs) & (own | enemy);
+            (blockers.count_ones() == 1 && blockers & own != 0)
+                .then(|| (blockers.trailing_zeros() as usize, sniper))
+        })
+    })
+}
+
+/// Every square between the king and rook and their landing squares must be
+/// empty apart from the two castling pieces, and the king may not start on,
+/// pass through or land on an attacked square.
+fn can_castle(board: &Board, ksq: usize, rook_sq: usize, side: usize) -> bool {
+    let us = board.turn;
+    if board.bitboard(us, PieceType::Rook) & (1u64 << rook_sq) == 0 {
+        return false;
+    }
+    let (king_to, rook_to) = castling_targets(us, side);
+    let king_path = row_span(ksq, king_to);
+    let must_be_empty =
+        (king_path | row_span(rook_sq, rook_to)) & !(1u64 << ksq) & !(1u64 << rook_sq);
+    if board.occupied() & must_be_empty != 0 {
+        return false;
+    }
+    let them = us.opponent();
+    !BitIter(king_path).any(|sq| is_square_attacked(board, sq, them))
+}
+
+// Knight placements among the five files left after the bishops and queen,
+// in Scharnagl numbering order
+const CHESS960_KNIGHTS: [(usize, usize); 10] = [
+    (0, 1),
+    (0, 2),
+    (0, 3),
+    (0, 4),
+    (1, 2),
+    (1, 3),
+    (1, 4),
+    (2, 3),
+    (2, 4),
+    (3, 4),
+];
+
+/// FEN of Chess960 start position `index` (0-959) in Scharnagl numbering,
+/// where 518 is the standard setup.
+pub fn chess960_fen(index: u16) -> String {
+    fn place_on_empty(rank: &mut [Option<char>; 8], nth: usize, piece: char) {
+        if let Some(slot) = rank.iter_mut().filter(|sq| sq.is_none()).nth(nth) {
+            *slot = Some(piece);
+        }
+    }
+
+    let mut rank = [None; 8];
+    let mut n = (index % 960) as usize;
+    rank[(n % 4) * 2 + 1] = Some('b');
+    n /= 4;
+    rank[(n % 4) * 2] = Some('b');
+    n /= 4;
+    place_on_empty(&mut rank, n % 6, 'q');
+    n /= 6;
+    // Place the later knight first so the earlier one's index is unaffected
+    let (first, second) = CHESS960_KNIGHTS[n];
+    place_on_empty(&mut rank, second, 'n');
+    place_on_empty(&mut rank, first, 'n');
+    for piece in ['r', 'k', 'r'] {
+        place_on_empty(&mut rank, 0, piece);
+    }
+
+    let black: String = rank.iter().map(|sq| sq.unwrap_or('r')).collect();
+    format!(
+        "{}/pppppppp/8/8/8/8/PPPPPPPP/{} w KQkq - 0 1",
+        black,
+        black.to_ascii_uppercase()
+    )
+}
+
+/// Counts the leaf nodes of the legal move tree `depth` plies deep. Used to
+/// validate move generation against published perft numbers.
+pub fn perft(board: &Board, depth: u8) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    let moves = generate_moves(board);
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+    moves
+        .iter()
+        .map(|m| {
+            let mut b_clone = board.clone();
+            b_clone.make_move(m);
+            perft(&b_clone, depth - 1)
+        })
+        .sum()
+}
+
+/// `perft` split by root move, in generation order, for finding which
+/// move's subtree a wrong count comes from.
+pub fn perft_divide(board: &Board, depth: u8) -> Vec<(Move, u64)> {
+    if depth == 0 {
+        return Vec::new();
+    }
+    generate_moves(board)
+        .into_iter()
+        .map(|m| {
+            let mut b_clone = board.clone();
+            b_clone.make_move(&m);
+            let nodes = perft(&b_clone, depth - 1);
+            (m, nodes)
+        })
+        .collect()
+}
+
+fn is_square_attacked(board: &Board, sq: usize, by: Color) -> bool {
+    board.attackers_bitboard(sq, by, board.occupied()) != 0
+}
+
+/// Whether `color`'s king is attacked. A side with no king counts as in
+/// check, so a kingless side to move with no moves is scored as mated rather
+/// than stalemated.
+fn is_in_check(board: &Board, color: Color) -> bool {
+    if color == board.turn {
+        return board.in_check();
+    }
+    let king = board.bitboard(color, PieceType::King);
+    if king == 0 {
+        return true;
+    }
+
+    is_square_attacked(board, king.trailing_zeros() as usize, color.opponent())
+}
+
+// Move ordering
+const CAPTURE_ORDER_BONUS: i32 = 2_000_000;
+const KILLER_ORDER_BONUS: i32 = 1_000_000;
+
+// Search bounds
+const INFINITY: i32 = 1_000_000;
+const MATE_THRESHOLD: i32 = 90_000;
+const MATE_SCORE: i32 = -100_000;
+const ASPIRATION_WINDOW: i32 = 50;
+const TIE_BREAK_MARGIN: i32 = 10;
+const LMR_MIN_DEPTH: u8 = 3;
+const LMR_FULL_DEPTH_MOVES: usize = 3;
+/// Margin by remaining depth within which a quiet move may still raise the
+/// static evaluation to alpha; beyond it the move is pruned.
+const FUTILITY_MARGINS: [i32; 3] = [0, 200, 400];
+/// Margin over a capture's material gain within which quiescence still
+/// searches it when the gain alone can't lift the score to alpha.
+const DELTA_MARGIN: i32 = 200;
+/// Non-pawn material on the board, both sides together, below which delta
+/// pruning is off: in endgames positional terms swing further than the
+/// margin allows for.
+const DELTA_MIN_MATERIAL: i32 = 1300;
+const STOP_POLL_INTERVAL: u64 = 1024;
+
+/// Value of the knights, bishops, rooks and queens of both sides.
+fn non_pawn_material(board: &Board) -> i32 {
+    [Color::White, Color::Black]
+        .into_iter()
+        .flat_map(|color| {
+            [
+                PieceType::Knight,
+                PieceType::Bishop,
+                PieceType::Rook,
+                PieceType::Queen,
+            ]
+            .map(|pt| board.bitboard(color, pt).count_ones() as i32 * pt.value())
+        })
+        .sum()
+}
+
+/// Most valuable victim, least valuable attacker ordering key for a
+/// capture, or `None` if `m` doesn't capture.
+fn mvv_lva(board: &Board, m: &Move) -> Option<i32> {
+    let victim = board.captured_piece(m)?;
+    let attacker = board
+        .get_piece(m.from_row, m.from_col)
+        .map_or(0, |p| p.piece_type.value());
+    Some(victim.piece_type.value() * 10 - attacker)
+}
+
+/// Default size of the transposition table in megabytes.
+pub const DEFAULT_HASH_MB: usize = 16;
+
+/// How a stored score relates to the true score of its position.
+#[derive(Clone, Copy, PartialEq)]
+enum Bound {
+    Exact,
+    /// The search failed high: the true score is at least this.
+    Lower,
+    /// The search failed low: the true score is at most this.
+    Upper,
+}
+
+/// One transposition table slot. The best move is packed as the origin and
+/// target squares and an index into `PROMOTION_PIECES` plus one, with 0
+/// meaning no move, so a slot takes 16 bytes.
+#[derive(Clone, Copy)]
+struct TtEntry {
+    key: u64,
+    score: i32,
+    best_move: u16,
+    depth: u8,
+    bound: Bound,
+}
+
+impl TtEntry {
+    const EMPTY: TtEntry = TtEntry {
+        key: 0,
+        score: 0,
+        best_move: 0,
+        depth: 0,
+        bound: Bound::Exact,
+    };
+}
+
+fn pack_move(m: &Move) -> u16 {
+    let promotion = m
+        .promotion
+        .and_then(|pt| PROMOTION_PIECES.iter().position(|&p| p == pt))
+        .map_or(0, |i| i + 1);
+    (m.from_square().index() | m.to_square().index() << 6 | promotion << 12) as u16
+}
+
+fn unpack_move(packed: u16) -> Option<Move> {
+    if packed == 0 {
+        return None;
+    }
+    let (from, to) = (packed as usize & 63, (packed as usize >> 6) & 63);
+    Some(Move {
+        from_row: from / 8,
+        from_col: from % 8,
+        to_row: to / 8,
+        to_col: to % 8,
+        promotion: (packed as usize >> 12)
+            .checked_sub(1)
+            .map(|i| PROMOTION_PIECES[i]),
+    })
+}
+
+/// Scores of searched positions by Zobrist key, in a fixed number of slots
+/// so memory stays within the budget however long the search runs. Each key
+/// has one slot; a new result replaces the old one unless the old one is for
+/// the same position and searched deeper.
+pub struct TranspositionTable {
+    entries: Vec<TtEntry>,
+}
+
+impl TranspositionTable {
+    /// The largest power-of-two number of slots that fits in `megabytes`,
+    /// and at least one.
+    pub fn new(megabytes: usize) -> Self {
+        TranspositionTable {
+            entries: vec![TtEntry::EMPTY; Self::slots_for(megabytes)],
+        }
+    }
+
+    fn slots_for(megabytes: usize) -> usize {
+        match megabytes * 1024 * 1024 / std::mem::size_of::<TtEntry>() {
+            0 => 1,
+            n => 1 << n.ilog2(),
+        }
+    }
+
+    /// Number of slots.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Memory taken by the slots.
+    pub fn size_bytes(&self) -> usize {
+        self.entries.len() * std::mem::size_of::<TtEntry>()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.fill(TtEntry::EMPTY);
+    }
+
+    fn slot(&self, key: u64) -> usize {
+        key as usize & (self.entries.len() - 1)
+    }
+
+    fn probe(&self, key: u64) -> Option<TtEntry> {
+        let entry = self.entries[self.slot(key)];
+        (entry.key == key).then_some(entry)
+    }
+
+    fn store(&mut self, key: u64, depth: u8, score: i32, bound: Bound, best_move: Option<&Move>) {
+        let slot = self.slot(key);
+        let old = self.entries[slot];
+        if old.key == key && old.depth > depth {
+            return;
+        }
+        self.entries[slot] = TtEntry {
+            key,
+            score,
+            best_move: best_move.map_or(0, pack_move),
+            depth,
+            bound,
+        };
+    }
+}
+
+/// A mate or tablebase score as a distance from the position it's stored
+/// for rather than from the root, so it stays right when the position turns
+/// up at another ply.
+fn score_to_tt(score: i32, ply: usize) -> i32 {
+    if score >= TB_WIN_THRESHOLD {
+        score + ply as i32
+    } else if score <= -TB_WIN_THRESHOLD {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+fn score_from_tt(score: i32, ply: usize) -> i32 {
+    if score >= TB_WIN_THRESHOLD {
+        score - ply as i32
+    } else if score <= -TB_WIN_THRESHOLD {
+        score + ply as i32
+    } else {
+        score
+    }
+}
+
+/// The outcome of a position with perfect play, for the side to move.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Wdl {
+    Loss,
+    Draw,
+    Win,
+}
+
+/// What a tablebase knows about a position.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TbResult {
+    pub wdl: Wdl,
+    /// Plies to the next capture or pawn move with best play, if the
+    /// tablebase gives it.
+    pub dtz: Option<u32>,
+}
+
+/// A source of endgame tablebase results, such as a wrapper around a
+/// Syzygy probing crate. The search asks it about every position below the
+/// root with at most `max_pieces` pieces on the board, kings included, and
+/// takes any answer in place of searching the position.
+pub trait TablebaseProvider: Send + Sync {
+    /// The result for `board`, or `None` if the position isn't covered.
+    fn probe(&self, board: &Board) -> Option<TbResult>;
+
+    /// Most pieces, kings included, in a position the tablebase covers.
+    fn max_pieces(&self) -> u32 {
+        7
+    }
+}
+
+/// The default provider, which covers no positions.
+pub struct NoTablebase;
+
+impl TablebaseProvider for NoTablebase {
+    fn probe(&self, _board: &Board) -> Option<TbResult> {
+        None
+    }
+
+    fn max_pieces(&self) -> u32 {
+        0
+    }
+}
+
+/// Score of a tablebase win at the root, less a point per ply so the
+/// search prefers the quickest way into one. Above any evaluation but below
+/// the mate scores, so a mate the search finds still ranks first.
+const TB_WIN_SCORE: i32 = 20_000;
+
+/// Lowest score counted as a tablebase win, leaving room for the plies
+/// taken off `TB_WIN_SCORE`.
+const TB_WIN_THRESHOLD: i32 = TB_WIN_SCORE - 1_000;
+
+// Minimax with Alpha-Beta
+pub struct Engine {
+    pub board: Board,
+    /// Nodes visited by the last search.
+    pub nodes: u64,
+    /// Depth of the last iteration the last search completed, or 0 if it
+    /// played a book move or didn't search.
+    pub depth: u8,
+    /// Score of the last search, or `None` if it played a book move or
+    /// didn't search.
+    pub score: Option<EvalReport>,
+    /// Principal variation of the last search: the move played followed by
+    /// the replies the search expects.
+    pub pv: Vec<Move>,
+    /// Play a book move without searching when the position is in the book.
+    pub use_book: bool,
+    /// Search late quiet moves at reduced depth first.
+    pub use_lmr: bool,
+    /// Order quiet moves by killer moves and the history table.
+    pub use_killers: bool,
+    /// Search each iteration in a narrow window around the previous score.
+    pub use_aspiration: bool,
+    /// Skip quiet moves near the leaves that can't plausibly reach alpha.
+    pub use_futility: bool,
+    /// Search moves that give check a ply deeper.
+    pub use_check_extensions: bool,
+    /// Skip captures in quiescence that can't plausibly reach alpha.
+    pub use_delta_pruning: bool,
+    /// Look up the pawn structure score in a table by pawn key rather
+    /// than computing it at every evaluation.
+    pub use_pawn_hash: bool,
+    /// Evaluation weights used by the search.
+    pub params: EvalParams,
+    /// When set, the move is picked reproducibly at random among those
+    /// scoring within `TIE_BREAK_MARGIN` of the best, and among book moves.
+    pub seed: Option<u64>,
+    /// Ends the search once it has visited this many nodes, playing the
+    /// best move of the last completed iteration. Unlike a time limit this
+    /// gives the same result on every machine.
+    pub max_nodes: Option<u64>,
+    /// Ends the search once it has run this long, playing the best move of
+    /// the last completed iteration. Ignored under wasm, which has no clock.
+    pub max_time: Option<Duration>,
+    /// Centipawns the engine counts a draw as losing, so positive values
+    /// make it play on in level positions rather than repeat. Zero scores
+    /// draws as even.
+    pub contempt: i32,
+    /// Size of the transposition table in megabytes. The table is set up
+    /// when a search starts and kept for later searches of the same size.
+    pub hash_mb: usize,
+    /// Endgame tablebase consulted below the root; `NoTablebase` by
+    /// default.
+    pub tablebase: Arc<dyn TablebaseProvider>,
+    /// Results of earlier searches of positions, by Zobrist key.
+    tt: TranspositionTable,
+    /// Pawn structure scores of pawn skeletons met in earlier evaluations.
+    pawn_table: PawnHashTable,
+    /// Best line found below each ply of the current search.
+    pv_table: Vec<Vec<Move>>,
+    /// Two quiet moves per ply that most recently caused a beta cutoff.
+    killers: Vec<[Option<Move>; 2]>,
+    /// Butterfly table of cutoff counts for quiet moves, indexed `[from][to]`.
+    history: [[i32; 64]; 64],
+    /// Zobrist keys of the positions played before `board`.
+    game_keys: Vec<u64>,
+    /// Zobrist keys of the positions on the current search path.
+    path_keys: Vec<u64>,
+    /// Set from another thread to end the search early.
+    stop: Arc<AtomicBool>,
+    /// Whether the current search has seen `stop` and is unwinding.
+    stopped: bool,
+    /// When the current search must end by, from `max_time`.
+    deadline: Option<Instant>,
+}
+
+impl Engine {
+    pub fn new(board: Board) -> Self {
+        Engine {
+            board,
+            nodes: 0,
+            depth: 0,
+            score: None,
+            pv: Vec::new(),
+            use_book: true,
+            use_lmr: true,
+            use_killers: true,
+            use_aspiration: true,
+            use_futility: true,
+            use_check_extensions: true,
+            use_delta_pruning: true,
+            use_pawn_hash: true,
+            params: EvalParams::default(),
+            seed: None,
+            max_nodes: None,
+            max_time: None,
+            contempt: 0,
+            hash_mb: DEFAULT_HASH_MB,
+            tablebase: Arc::new(NoTablebase),
+            tt: TranspositionTable {
+                entries: Vec::new(),
+            },
+            pawn_table: PawnHashTable::new(),
+            pv_table: Vec::new(),
+            killers: Vec::new(),
+            history: [[0; 64]; 64],
+            game_keys: Vec::new(),
+            path_keys: Vec::new(),
+            stop: Arc::new(AtomicBool::new(false)),
+            stopped: false,
+            deadline: None,
+        }
+    }
+
+    /// A flag that makes a running search return as soon as it's set. The
+    /// search then plays the best move of its last completed iteration.
+    pub fn stop_flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.stop)
+    }
+
+    /// Records the positions played before `board`, oldest first, so the
+    /// search scores a return to any of them as a draw by repetition.
+    pub fn set_game_history(&mut self, positions: &[Board]) {
+        self.game_keys = positions.iter().map(Board::zobrist_key).collect();
+    }
+
+    pub fn search(&mut self, depth: u8, excluded_moves: &[Move]) -> Option<Move> {
+        self.search_reporting(depth, excluded_moves, &mut |_| {})
+    }
+
+    /// `search`, calling `on_iter` with the engine after each completed
+    /// iteration, once `depth`, `score` and `pv` describe it.
+    fn search_reporting(
+        &mut self,
+        depth: u8,
+        excluded_moves: &[Move],
+        on_iter: &mut dyn FnMut(&Engine),
+    ) -> Option<Move> {
+        self.start_search(depth);
+        if self.is_game_over() {
+            return None;
+        }
+
+        let mut moves = generate_moves(&self.board);
+
+        // Filter excluded moves
+        if !excluded_moves.is_empty() {
+            moves.retain(|m| !excluded_moves.contains(m));
+        }
+        if moves.is_empty() {
+            return None;
+        }
+
+        if self.use_book {
+            let book: Vec<Move> = book_moves(&self.board)
+                .into_iter()
+                .filter(|m| moves.contains(m))
+                .collect();
+            if !book.is_empty() {
+                let m = book[(self.random() % book.len() as u64) as usize].clone();
+                self.pv = vec![m.clone()];
+                return Some(m);
+            }
+        }
+
+        // Iterative deepening, each iteration searching the previous best move first
+        let mut best_move = None;
+        let mut prev_score = None;
+        for d in 1..=depth {
+            let (m, score) = self.search_aspiration(&moves, d, prev_score);
+            if self.stopped {
+                break;
+            }
+            if let Some(m) = &m {
+                if let Some(pos) = moves.iter().position(|x| x == m) {
+                    let best = moves.remove(pos);
+                    moves.insert(0, best);
+                }
+            }
+            best_move = m;
+            prev_score = Some(score);
+            self.depth = d;
+            self.pv = self.pv_table[0].clone();
+            self.score = Some(EvalReport::from_score(score));
+            on_iter(self);
+            // A mate within the nominal depth is the quickest there is, as a
+            // shorter one would have turned up in an earlier iteration. A
+            // longer one may have come from check extensions, and deeper
+            // iterations may still find a quicker mate
+            if score >= MATE_THRESHOLD && -MATE_SCORE - score <= d as i32 {
+                break;
+            }
+        }
+        self.score = prev_score.map(EvalReport::from_score);
+        if self.stopped {
+            // Stopped before the first iteration finished
+            let best_move = best_move.or_else(|| moves.first().cloned());
+            if self.pv.is_empty() {
+                self.pv.extend(best_move.clone());
+            }
+            return best_move;
+        }
+
+        match (self.seed, best_move, prev_score) {
+            (Some(_), Some(best), Some(score)) if score.abs() < MATE_THRESHOLD => {
+                let picked = self.pick_near_best(&moves, best, score, depth);
+                if self.pv.first() != Some(&picked) {
+                    self.pv = vec![picked.clone()];
+                }
+                Some(picked)
+            }
+            (_, best_move, _) => best_move,
+        }
+    }
+
+    /// Random choice among the root moves scoring at least `best_score`
+    /// minus `TIE_BREAK_MARGIN`, each checked with a null-window search.
+    fn pick_near_best(&mut self, moves: &[Move], best: Move, best_score: i32, depth: u8) -> Move {
+        let bound = best_score - TIE_BREAK_MARGIN;
+        let mut candidates = vec![best];
+        for m in moves {
+            if *m == candidates[0] {
+                continue;
+            }
+            let mut new_board = self.board.clone();
+            new_board.make_move(m);
+            if -self.alpha_beta(&new_board, depth - 1, 1, -bound, -bound + 1) >= bound {
+                candidates.push(m.clone());
+            }
+        }
+        let i = (self.random() % candidates.len() as u64) as usize;
+        candidates.swap_remove(i)
+    }
+
+    /// Derived from the seed and the position when seeded, so a given seed
+    /// always plays the same game; otherwise from `random_u64`.
+    fn random(&self) -> u64 {
+        match self.seed {
+            Some(seed) => splitmix64(seed ^ self.board.zobrist_key()).1,
+            None => random_u64(),
+        }
+    }
+
+    /// The best `n` root moves with their scores for the side to move, best
+    /// first. Each move is searched against the `n`th best score so far and
+    /// dropped if it can't beat it, so every returned score is exact.
+    pub fn top_moves(&mut self, depth: u8, n: usize) -> Vec<(Move, i32)> {
+        let depth = depth.max(1);
+        self.start_search(depth);
+        if n == 0 || self.is_game_over() {
+            return Vec::new();
+        }
+
+        let board = self.board.clone();
+        let mut moves = generate_moves(&board);
+        moves.sort_by_cached_key(|m| -self.move_order_score(&board, m, 0));
+
+        let mut top: Vec<(Move, i32)> = Vec::with_capacity(n.min(moves.len()) + 1);
+        for m in moves {
+            let floor = if top.len() == n {
+                top[n - 1].1
+            } else {
+                -INFINITY
+            };
+            let mut new_board = board.clone();
+            new_board.make_move(&m);
+            let score = -self.alpha_beta(&new_board, depth - 1, 1, -INFINITY, -floor);
+            if self.stopped {
+                break;
+            }
+            if score > floor {
+                let pos = top
+                    .iter()
+                    .position(|(_, s)| *s < score)
+                    .unwrap_or(top.len());
+                top.insert(pos, (m, score));
+                top.truncate(n);
+            }
+        }
+        top
+    }
+
+    /// Counts a node and reports whether the search should unwind, reading
+    /// the shared stop flag and the clock only every `STOP_POLL_INTERVAL`
+    /// nodes.
+    fn poll_stop(&mut self) -> bool {
+        if self.stopped {
+            return true;
+        }
+        self.nodes += 1;
+        if self.max_nodes.is_some_and(|max| self.nodes >= max)
+            || (self.nodes.is_multiple_of(STOP_POLL_INTERVAL)
+                && (self.stop.load(Ordering::Relaxed)
+                    || self.deadline.is_some_and(|d| Instant::now() >= d)))
+        {
+            self.stopped = true;
+        }
+        self.stopped
+    }
+
+    fn start_search(&mut self, depth: u8) {
+        self.stopped = false;
+        self.depth = 0;
+        // Instant::now panics under wasm
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            self.deadline = self.max_time.map(|t| Instant::now() + t);
+        }
+        self.nodes = 0;
+        self.score = None;
+        self.pv.clear();
+        // Check extensions may take the search up to twice the nominal depth
+        let max_ply = depth as usize * 2;
+        self.pv_table = vec![Vec::new(); max_ply + 2];
+        self.killers = vec![[None, None]; max_ply + 1];
+        self.history = [[0; 64]; 64];
+        self.path_keys = vec![self.board.zobrist_key()];
+        if self.tt.len() != TranspositionTable::slots_for(self.hash_mb) {
+            self.tt = TranspositionTable::new(self.hash_mb);
+        }
+    }
+
+    /// A finished game has no best move, even where the rules would let the
+    /// side to move play on in a drawn position.
+    fn is_game_over(&self) -> bool {
+        let key = self.board.zobrist_key();
+        self.board.status().is_game_over()
+            || self.game_keys.iter().filter(|&&k| k == key).count() >= 2
+    }
+
+    /// Searches the root inside a narrow window around the previous
+    /// iteration's score, re-searching with the failing bound opened up when
+    /// the score falls outside it. Mate scores always get the full window.
+    fn search_aspiration(
+        &mut self,
+        moves: &[Move],
+        depth: u8,
+        prev_score: Option<i32>,
+    ) -> (Option<Move>, i32) {
+        let (mut alpha, mut beta) = match prev_score {
+            Some(s) if self.use_aspiration && s.abs() < MATE_THRESHOLD => {
+                (s - ASPIRATION_WINDOW, s + ASPIRATION_WINDOW)
+            }
+            _ => (-INFINITY, INFINITY),
+        };
+
+        loop {
+            let (best_move, score) = self.search_root(moves, depth, alpha, beta);
+            if self.stopped {
+                return (best_move, score);
+            }
+            if score <= alpha && alpha > -INFINITY {
+                alpha = -INFINITY;
+            } else if score >= beta && beta < INFINITY {
+                beta = INFINITY;
+            } else {
+                return (best_move, score);
+            }
+        }
+    }
+
+    fn search_root(
+        &mut self,
+        moves: &[Move],
+        depth: u8,
+        mut alpha: i32,
+        beta: i32,
+    ) -> (Option<Move>, i32) {
+        let mut best_move = None;
+        let mut best_score = -INFINITY;
+
+        for m in moves {
+            let mut new_board = self.board.clone();
+            new_board.make_move(m);
+
+            let score = -self.alpha_beta(&new_board, depth - 1, 1, -beta, -alpha);
+            if self.stopped {
+                break;
+            }
+
+            if score > best_score {
+                best_score = score;
+                best_move = Some(m.clone());
+                self.update_pv(0, m);
+            }
+            alpha = alpha.max(score);
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        (best_move, best_score)
+    }
+
+    fn alpha_beta(
+        &mut self,
+        board: &Board,
+        depth: u8,
+        ply: usize,
+        mut alpha: i32,
+        beta: i32,
+    ) -> i32 {
+        if self.poll_stop() {
+            return 0;
+        }
+        self.pv_table[ply].clear();
+
+        // Any repetition of an earlier position is scored as a draw
+        let key = board.zobrist_key();
+        if self.path_keys.contains(&key) || self.game_keys.contains(&key) {
+            return self.draw_score(board);
+        }
+
+        // Checkmate and stalemate are found before dropping into the
+        // quiescence search, which would otherwise stand pat on material and
+        // score a stalemate as a win
+        let mut moves = generate_moves(board);
+        let in_check = is_in_check(board, board.turn);
+        if moves.is_empty() {
+            if in_check {
+                // Mates closer to the root score further from zero, so the
+                // quickest mate is preferred and the longest defence chosen
+                return MATE_SCORE + ply as i32;
+            }
+            return self.draw_score(board); // Stalemate
+        }
+        if board.halfmove_clock >= 100 {
+            return self.draw_score(board);
+        }
+
+        if board.occupied().count_ones() <= self.tablebase.max_pieces() {
+            if let Some(result) = self.tablebase.probe(board) {
+                return match result.wdl {
+                    Wdl::Win => TB_WIN_SCORE - ply as i32,
+                    Wdl::Loss => -TB_WIN_SCORE + ply as i32,
+                    Wdl::Draw => self.draw_score(board),
+                };
+            }
+        }
+
+        if depth == 0 {
+            return self.quiescence(board, alpha, beta);
+        }
+
+        // A stored result from a search at least this deep settles the
+        // position if its bound allows; otherwise its best move goes first.
+        // The move is checked against the legal ones in case two positions
+        // share a slot and a key
+        let entry = self.tt.probe(key);
+        let tt_move = entry
+            .and_then(|e| unpack_move(e.best_move))
+            .filter(|m| moves.contains(m));
+        if let Some(entry) = entry.filter(|e| e.depth >= depth) {
+            let score = score_from_tt(entry.score, ply);
+            let settled = match entry.bound {
+                Bound::Exact => true,
+                Bound::Lower => score >= beta,
+                Bound::Upper => score <= alpha,
+            };
+            if settled {
+                if entry.bound == Bound::Exact {
+                    self.pv_table[ply].extend(tt_move);
+                }
+                return score;
+            }
+        }
+
+        moves.sort_by_cached_key(|m| -self.move_order_score(board, m, ply));
+        if let Some(pos) = tt_move.and_then(|tm| moves.iter().position(|m| *m == tm)) {
+            let m = moves.remove(pos);
+            moves.insert(0, m);
+        }
+
+        // Futility pruning: this close to the leaves, a quiet move is
+        // unlikely to gain more than the margin, so when even that leaves
+        // the static evaluation below alpha the move isn't searched. Mate
+        // scores are left alone, as the margin means nothing next to them
+        let futility_eval = (self.use_futility
+            && (depth as usize) < FUTILITY_MARGINS.len()
+            && !in_check
+            && alpha.abs() < MATE_THRESHOLD)
+            .then(|| self.static_eval(board) + FUTILITY_MARGINS[depth as usize])
+            .filter(|&eval| eval <= alpha);
+
+        self.path_keys.push(key);
+        let original_alpha = alpha;
+        let mut max_eval = -INFINITY;
+        let mut best_move = None;
+        for (i, m) in moves.into_iter().enumerate() {
+            let quiet = board.captured_piece(&m).is_none();
+            let mut b_clone = board.clone();
+            b_clone.make_move(&m);
+            let gives_check = is_in_check(&b_clone, b_clone.turn);
+
+            if let Some(eval) = futility_eval {
+                if quiet
+                    && !gives_check
+                    && !matches!(board.classify(&m), MoveKind::Promotion { .. })
+                {
+                    max_eval = max_eval.max(eval);
+                    continue;
+                }
+            }
+
+            // Late move reductions: quiet moves ordered late are searched a
+            // ply shallower with a null window, and only searched again at
+            // full depth if they turn out to raise alpha
+            let reduce = self.use_lmr
+                && depth >= LMR_MIN_DEPTH
+                && i >= LMR_FULL_DEPTH_MOVES
+                && quiet
+                && !in_check
+                && !gives_check
+                && !matches!(board.classify(&m), MoveKind::Promotion { .. });
+            let mut eval = if reduce {
+                -self.alpha_beta(&b_clone, depth - 2, ply + 1, -alpha - 1, -alpha)
+            } else {
+                alpha + 1
+            };
+            if eval > alpha {
+                // Check extension: a check is searched a ply deeper so a
+                // forcing line isn't cut off at the horizon, as long as the
+                // line stays within the plies the search has room for
+                let extend = self.use_check_extensions
+                    && gives_check
+                    && ply + (depth as usize) < self.max_ply();
+                let new_depth = depth - 1 + extend as u8;
+                eval = -self.alpha_beta(&b_clone, new_depth, ply + 1, -beta, -alpha);
+            }
+
+            if eval > max_eval {
+                max_eval = eval;
+                best_move = Some(m.clone());
+            }
+            if eval > alpha {
+                alpha = eval;
+                self.update_pv(ply, &m);
+            }
+            if beta <= alpha {
+                if quiet {
+                    self.store_quiet_cutoff(&m, depth, ply);
+                }
+                break;
+            }
+        }
+        self.path_keys.pop();
+
+        // A stopped search's scores are unfinished
+        if !self.stopped {
+            let bound = if max_eval >= beta {
+                Bound::Lower
+            } else if max_eval > original_alpha {
+                Bound::Exact
+            } else {
+                Bound::Upper
+            };
+            let score = score_to_tt(max_eval, ply);
+            self.tt.store(key, depth, score, bound, best_move.as_ref());
+        }
+        max_eval
+    }
+
+    /// Deepest ply the current search may reach with extensions.
+    fn max_ply(&self) -> usize {
+        self.killers.len() - 1
+    }
+
+    /// Score of a draw for the side to move at `board`: `contempt` below
+    /// zero for the side the engine is playing, and above it for the other.
+    fn draw_score(&self, board: &Board) -> i32 {
+        if board.turn == self.board.turn {
+            -self.contempt
+        } else {
+            self.contempt
+        }
+    }
+
+    /// Makes `m` followed by the line below it the best line at `ply`.
+    fn update_pv(&mut self, ply: usize, m: &Move) {
+        let (this, below) = self.pv_table.split_at_mut(ply + 1);
+        this[ply].clear();
+        this[ply].push(m.clone());
+        this[ply].extend_from_slice(&below[0]);
+    }
+
+    /// The static evaluation relative to the side to move, taking the
+    /// pawn structure score from the pawn hash table.
+    fn static_eval(&mut self, board: &Board) -> i32 {
+        let eval = if self.use_pawn_hash {
+            let pawns = self.pawn_table.score(board, &self.params);
+            evaluate_terms(board, &self.params, pawns).total()
+        } else {
+            evaluate(board, &self.params)
+        };
+        match board.turn {
+            Color::White => eval,
+            Color::Black => -eval,
+        }
+    }
+
+    /// Searches captures only until the position is quiet, so the static
+    /// evaluation is never taken in the middle of an exchange. The side to
+    /// move may always stand pat instead of capturing, and captures that
+    /// lose material by static exchange are skipped.
+    fn quiescence(&mut self, board: &Board, mut alpha: i32, beta: i32) -> i32 {
+        if self.poll_stop() {
+            return 0;
+        }
+
+        // Negamax scores are relative to the side to move
+        let stand_pat = self.static_eval(board);
+        if stand_pat >= beta {
+            return stand_pat;
+        }
+        alpha = alpha.max(stand_pat);
+
+        let mut captures: Vec<Move> = generate_captures(board)
+            .into_iter()
+            .filter(|m| board.see(m) >= 0)
+            .collect();
+        captures.sort_by_cached_key(|m| -mvv_lva(board, m).unwrap_or(0));
+
+        // Delta pruning: a capture that leaves the score below alpha even
+        // after winning the piece and a margin isn't searched. A pawn about
+        // to promote or an endgame can swing further, so those keep every
+        // capture
+        let seventh_rank =
+            (home_row(board.turn.opponent()) as i32 - board.turn.forward_dir()) as usize;
+        let delta_pruning = self.use_delta_pruning
+            && board.bitboard(board.turn, PieceType::Pawn) & (0xFF << (seventh_rank * 8)) == 0
+            && non_pawn_material(board) >= DELTA_MIN_MATERIAL;
+
+        let mut max_eval = stand_pat;
+        for m in captures {
+            if delta_pruning {
+                let gain = board.captured_piece(&m).map_or(0, |p| p.piece_type.value());
+                if stand_pat + gain + DELTA_MARGIN <= alpha {
+                    continue;
+                }
+            }
+            let mut b_clone = board.clone();
+            b_clone.make_move(&m);
+            let eval = -self.quiescence(&b_clone, -beta, -alpha);
+            max_eval = max_eval.max(eval);
+            alpha = alpha.max(eval);
+            if beta <= alpha {
+                break;
+            }
+        }
+        max_eval
+    }
+
+    /// Winning and even captures first (MVV-LVA), then killer moves, then
+    /// quiet moves by history, then captures that lose material.
+    fn move_order_score(&self, board: &Board, m: &Move, ply: usize) -> i32 {
+        // Underpromotions are hardly ever best, so they go last
+        if m.promotion.is_some_and(|pt| pt != PieceType::Queen) {
+            return -CAPTURE_ORDER_BONUS;
+        }
+        if let Some(score) = mvv_lva(board, m) {
+            let see = board.see(m);
+            return if see >= 0 {
+                CAPTURE_ORDER_BONUS + score
+            } else {
+                see
+            };
+        }
+        if !self.use_killers {
+            return 0;
+        }
+        let killers = &self.killers[ply];
+        if killers[0].as_ref() == Some(m) {
+            return KILLER_ORDER_BONUS;
+        }
+        if killers[1].as_ref() == Some(m) {
+            return KILLER_ORDER_BONUS - 1;
+        }
+        self.history[m.from_row * 8 + m.from_col][m.to_row * 8 + m.to_col]
+    }
+
+    fn store_quiet_cutoff(&mut self, m: &Move, depth: u8, ply: usize) {
+        let killers = &mut self.killers[ply];
+        if killers[0].as_ref() != Some(m) {
+            killers[1] = killers[0].take();
+            killers[0] = Some(m.clone());
+        }
+        self.history[m.from_row * 8 + m.from_col][m.to_row * 8 + m.to_col] +=
+            depth as i32 * depth as i32;
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub fn get_best_move(fen: &str, depth: u8) -> Result<JsValue, JsValue> {
+    let best_move = get_best_move_core(fen, depth, &[]);
+    match best_move {
+        Some(m) => Ok(serde_wasm_bindgen::to_value(&m).map_err(|e| e.to_string())?),
+        None => match game_status_core(fen) {
+            status if status.is_game_over() => Err(JsValue::from_str(status.as_str())),
+            _ => Err(JsValue::from_str("No moves available")),
+        },
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub fn game_status(fen: &str) -> String {
+    game_status_core(fen).as_str().to_string()
+}
+
+pub fn game_status_core(fen: &str) -> GameStatus {
+    Board::from_fen(fen).status()
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub fn material_balance(fen: &str) -> i32 {
+    material_balance_core(fen)
+}
+
+pub fn material_balance_core(fen: &str) -> i32 {
+    Board::from_fen(fen).material_balance()
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub fn position_hash(fen: &str) -> String {
+    position_hash_core(fen)
+}
+
+/// `Board::hash` of the position as 16 hex digits, since JavaScript numbers
+/// can't hold all 64 bits.
+pub fn position_hash_core(fen: &str) -> String {
+    format!("{:016x}", Board::from_fen(fen).hash())
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub fn piece_counts(fen: &str) -> Result<JsValue, JsValue> {
+    Ok(serde_wasm_bindgen::to_value(&piece_counts_core(fen)).map_err(|e| e.to_string())?)
+}
+
+pub fn piece_counts_core(fen: &str) -> PieceCounts {
+    Board::from_fen(fen).piece_counts()
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub fn evaluate_position(fen: &str, depth: u8) -> Result<JsValue, JsValue> {
+    Ok(
+        serde_wasm_bindgen::to_value(&evaluate_position_core(fen, depth))
+            .map_err(|e| e.to_string())?,
+    )
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub fn analyze(fen: &str, depth: u8) -> Result<JsValue, JsValue> {
+    Ok(serde_wasm_bindgen::to_value(&analyze_core(fen, depth)).map_err(|e| e.to_string())?)
+}
+
+/// Searches `fen` to `depth` without the opening book and reports the best
+/// move, its score for the side to move and the expected line. Drawn
+/// positions score `Cp(0)`.
+pub fn analyze_core(fen: &str, depth: u8) -> Analysis {
+    let config = SearchConfig {
+        depth,
+        use_book: false,
+        ..SearchConfig::default()
+    };
+    let result = search(fen, &config);
+    Analysis {
+        best_move: result.best_move,
+        score: result.score,
+        pv: result.pv,
+    }
+}
+
+/// Searches `fen` to `depth` and reports the score for the side to move.
+/// Drawn positions score `Cp(0)`.
+pub fn evaluate_position_core(fen: &str, depth: u8) -> EvalReport {
+    analyze_core(fen, depth).score
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub fn top_moves(fen: &str, depth: u8, n: usize) -> Result<JsValue, JsValue> {
+    Ok(serde_wasm_bindgen::to_value(&top_moves_core(fen, depth, n)).map_err(|e| e.to_string())?)
+}
+
+/// The best `n` moves in UCI notation with centipawn scores for the side to
+/// move, best first.
+pub fn top_moves_core(fen: &str, depth: u8, n: usize) -> Vec<(String, i32)> {
+    let board = Board::from_fen(fen);
+    let mut engine = Engine::new(board.clone());
+    engine
+        .top_moves(depth, n)
+        .into_iter()
+        .map(|(m, score)| (board.move_to_uci(&m), score))
+        .collect()
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub fn attacked_squares(fen: &str, by: &str) -> Result<JsValue, JsValue> {
+    let by = match by {
+        "w" => Color::White,
+        "b" => Color::Black,
+        _ => return Err(JsValue::from_str("color must be 'w' or 'b'")),
+    };
+    Ok(serde_wasm_bindgen::to_value(&attacked_squares_core(fen, by)).map_err(|e| e.to_string())?)
+}
+
+/// Squares attacked by `by` in algebraic notation, for shading the squares
+/// a king can't move to.
+pub fn attacked_squares_core(fen: &str, by: Color) -> Vec<String> {
+    Board::from_fen(fen)
+        .attacked_squares(by)
+        .into_iter()
+        .map(Square::to_algebraic)
+        .collect()
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub fn legal_moves_from(fen: &str, square: &str) -> Result<JsValue, JsValue> {
+    Ok(
+        serde_wasm_bindgen::to_value(&legal_moves_from_core(fen, square))
+            .map_err(|e| e.to_string())?,
+    )
+}
+
+/// Legal moves in UCI notation for the piece on `square`, or none if the
+/// square is empty, holds a piece of the side not to move, or isn't a
+/// square. Castling is written as in `Board::move_to_uci`, and each
+/// promotion piece is a separate move.
+pub fn legal_moves_from_core(fen: &str, square: &str) -> Vec<String> {
+    let Some(from) = Square::from_algebraic(square) else {
+        return Vec::new();
+    };
+    let board = Board::from_fen(fen);
+    generate_moves(&board)
+        .into_iter()
+        .filter(|m| m.from_square() == from)
+        .map(|m| board.move_to_uci(&m))
+        .collect()
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub fn search_position(fen: &str, depth: u8) -> Result<JsValue, JsValue> {
+    let config = SearchConfig {
+        depth,
+        use_book: false,
+        ..SearchConfig::default()
+    };
+    Ok(serde_wasm_bindgen::to_value(&search(fen, &config)).map_err(|e| e.to_string())?)
+}
+
+/// Searches `fen` as `config` describes. The other search functions are
+/// shorthands for common configurations of this one.
+pub fn search(fen: &str, config: &SearchConfig) -> SearchResult {
+    let mut engine = configured_engine(fen, config);
+    let started = search_clock();
+    let best_move = engine.search(config.depth, &config.excluded);
+    search_result(&engine, best_move, started)
+}
+
+/// Searches `fen` as `config` describes, calling `on_iter` with the result
+/// so far after each completed iteration of the deepening, so a UI can show
+/// the analysis as it improves. A book move or a finished game completes
+/// no iterations.
+pub fn search_streaming<F: FnMut(&SearchResult)>(fen: &str, config: &SearchConfig, mut on_iter: F) {
+    let mut engine = configured_engine(fen, config);
+    let started = search_clock();
+    engine.search_reporting(config.depth, &config.excluded, &mut |engine| {
+        on_iter(&search_result(engine, engine.pv.first().cloned(), started))
+    });
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub fn search_position_streaming(
+    fen: &str,
+    depth: u8,
+    on_iter: &js_sys::Function,
+) -> Result<(), JsValue> {
+    let config = SearchConfig {
+        depth,
+        use_book: false,
+        ..SearchConfig::default()
+    };
+    let mut error = None;
+    search_streaming(fen, &config, |result| {
+        let outcome = serde_wasm_bindgen::to_value(result)
+            .map_err(|e| JsValue::from(e.to_string()))
+            .and_then(|value| on_iter.call1(&JsValue::NULL, &value));
+        if let Err(e) = outcome {
+            error.get_or_insert(e);
+        }
+    });
+    error.map_or(Ok(()), Err)
+}
+
+fn configured_engine(fen: &str, config: &SearchConfig) -> Engine {
+    let mut engine = Engine::new(Board::from_fen(fen));
+    engine.use_book = config.use_book;
+    engine.params = config.eval.clone();
+    engine.contempt = config.contempt;
+    engine.seed = config.seed;
+    engine.max_nodes = config.max_nodes;
+    engine.max_time = config.max_millis.map(Duration::from_millis);
+    engine.hash_mb = config.hash_mb;
+    engine
+}
+
+/// When a search started, or `None` under wasm, which has no clock.
+fn search_clock() -> Option<Instant> {
+    (!cfg!(target_arch = "wasm32")).then(Instant::now)
+}
+
+/// `engine`'s last search as a `SearchResult`.
+fn search_result(
+    engine: &Engine,
+    best_move: Option<Move>,
+    started: Option<Instant>,
+) -> SearchResult {
+    let score = match engine.score {
+        Some(report) => report,
+        None if engine.board.status() == GameStatus::Checkmate => EvalReport::Mate(0),
+        None => EvalReport::Cp(0),
+    };
+    SearchResult {
+        best_move,
+        score,
+        pv: engine.pv.clone(),
+        nodes: engine.nodes,
+        depth: engine.depth,
+        time_ms: started.map_or(0, |t| t.elapsed().as_millis() as u64),
+    }
+}
+
+pub fn get_best_move_core(fen: &str, depth: u8, excluded_moves: &[Move]) -> Option<Move> {
+    let config = SearchConfig {
+        depth,
+        excluded: excluded_moves.to_vec(),
+        ..SearchConfig::default()
+    };
+    search(fen, &config).best_move
+}
+
+/// Searches `fen` to `depth` without the opening book, dealing the root
+/// moves out between `threads` threads that each search their share on
+/// their own and then keeping the best. The threads search with a full
+/// window rather than aspiration windows, so each thread's score is exact
+/// and the scores can be compared. On a tie the move generated first wins,
+/// so the result doesn't depend on thread timing.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn get_best_move_parallel(fen: &str, depth: u8, threads: usize) -> Option<Move> {
+    let board = Board::from_fen(fen);
+    let moves = generate_moves(&board);
+    let threads = threads.clamp(1, moves.len().max(1));
+
+    let results: Vec<(Move, EvalReport)> = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..threads)
+            .map(|t| {
+                // Each thread excludes every move dealt to the others
+                let excluded: Vec<Move> = moves
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| i % threads != t)
+                    .map(|(_, m)| m.clone())
+                    .collect();
+                let mut engine = Engine::new(board.clone());
+                engine.use_book = false;
+                engine.use_aspiration = false;
+                scope.spawn(move || {
+                    let best = engine.search(depth, &excluded)?;
+                    Some((best, engine.score?))
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .filter_map(|h| h.join().ok().flatten())
+            .collect()
+    });
+
+    let best_rank = results.iter().map(|(_, score)| score.rank()).max()?;
+    moves.into_iter().find(|m| {
+        results
+            .iter()
+            .any(|(best, score)| best == m && score.rank() == best_rank)
+    })
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub fn get_best_move_nodes(fen: &str, max_nodes: u64) -> Result<String, JsValue> {
+    match get_best_move_nodes_core(fen, max_nodes) {
+        Some(m) => Ok(m.to_coords()),
+        None => match game_status_core(fen) {
+            status if status.is_game_over() => Err(JsValue::from_str(status.as_str())),
+            _ => Err(JsValue::from_str("No moves available")),
+        },
+    }
+}
+
+/// Deepens the search until it has visited `max_nodes` nodes and returns
+/// the best move of the last iteration it completed.
+pub fn get_best_move_nodes_core(fen: &str, max_nodes: u64) -> Option<Move> {
+    let config = SearchConfig {
+        depth: u8::MAX,
+        max_nodes: Some(max_nodes),
+        ..SearchConfig::default()
+    };
+    search(fen, &config).best_move
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub fn get_best_move_seeded(fen: &str, depth: u8, seed: u64) -> Result<JsValue, JsValue> {
+    match get_best_move_seeded_core(fen, depth, seed) {
+        Some(m) => Ok(serde_wasm_bindgen::to_value(&m).map_err(|e| e.to_string())?),
+        None => match game_status_core(fen) {
+            status if status.is_game_over() => Err(JsValue::from_str(status.as_str())),
+            _ => Err(JsValue::from_str("No moves available")),
+        },
+    }
+}
+
+/// Like `get_best_move_core`, but varies between near-equal moves in a way
+/// that is reproducible for a given `seed`.
+pub fn get_best_move_seeded_core(fen: &str, depth: u8, seed: u64) -> Option<Move> {
+    let config = SearchConfig {
+        depth,
+        seed: Some(seed),
+        ..SearchConfig::default()
+    };
+    search(fen, &config).best_move
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn play(fen: &str, moves: &[&str]) -> Board {
+        let mut board = Board::from_fen(fen);
+        for coords in moves {
+            let m = Move::from_coords(coords).unwrap();
+            assert!(board.is_legal(&m), "{} in {}", coords, board.to_fen());
+            board.make_move(&m);
+        }
+        board
+    }
+
+    fn coords(moves: &[Move]) -> Vec<String> {
+        let mut coords: Vec<String> = moves.iter().map(Move::to_coords).collect();
+        coords.sort();
+        coords
+    }
+
+    // Pawn captures on the edge files
+
+    /// Diagonal moves of the pawn on `from`.
+    fn pawn_captures(board: &Board, from: &str) -> Vec<String> {
+        let from = Square::from_algebraic(from).unwrap();
+        let moves: Vec<Move> = generate_moves(board)
+            .into_iter()
+            .filter(|m| m.from_square() == from && m.from_col != m.to_col)
+            .collect();
+        coords(&moves)
+    }
+
+    #[test]
+    fn edge_file_pawn_captures() {
+        // Enemy pieces on both diagonals' wrapped squares as well as the
+        // real target
+        let cases = [
+            ("4k3/8/8/1p5p/P6p/7p/8/4K3 w - - 0 1", "a4", "a4b5"),
+            ("4k3/8/8/p5p1/7P/p7/p7/4K3 w - - 0 1", "h4", "h4g5"),
+            ("4k3/8/7P/p7/1P5P/7P/8/4K3 b - - 0 1", "a5", "a5b4"),
+            ("4k3/8/P7/P6p/P5P1/8/8/4K3 b - - 0 1", "h5", "h5g4"),
+        ];
+        for (fen, from, capture) in cases {
+            assert_eq!(
+                pawn_captures(&Board::from_fen(fen), from),
+                [capture],
+                "{}",
+                fen
+            );
+        }
+    }
+
+    #[test]
+    fn edge_file_en_passant() {
+        let cases = [
+            ("4k3/1p6/8/P6p/7p/8/8/4K3 b - - 0 1", "b7b5", "a5", "a5b6"),
+            ("4k3/6p1/p7/p6P/8/8/8/4K3 b - - 0 1", "g7g5", "h5", "h5g6"),
+            ("4k3/8/8/7P/p6P/8/1P6/4K3 w - - 0 1", "b2b4", "a4", "a4b3"),
+            ("4k3/8/8/P7/P6p/8/6P1/4K3 w - - 0 1", "g2g4", "h4", "h4g3"),
+        ];
+        for (fen, push, from, capture) in cases {
+            let board = play(fen, &[push]);
+            assert_eq!(pawn_captures(&board, from), [capture], "{}", fen);
+            let m = Move::from_coords(capture).unwrap();
+            assert!(board.is_en_passant(&m));
+        }
+    }
+
+    // Legal move generation against a brute-force filter
+
+    /// Every move `move_legality` accepts, found by trying each of the side
+    /// to move's pieces on every square, with each promotion choice for
+    /// pawns reaching the last rank. Its final check plays the move on a
+    /// copy and looks for check, with no pin or evasion logic.
+    fn brute_force_moves(board: &Board) -> Vec<Move> {
+        let pawns = board.bitboard(board.turn, PieceType::Pawn);
+        let last_row = home_row(board.turn.opponent());
+        let mut moves = Vec::new();
+        for from in BitIter(board.occupancy(board.turn)) {
+            for to in 0..64 {
+                let promotions = if pawns & (1u64 << from) != 0 && to / 8 == last_row {
+                    PROMOTION_PIECES.map(Some).to_vec()
+                } else {
+                    vec![None]
+                };
+                for promotion in promotions {
+                    let m = Move {
+                        from_row: from / 8,
+                        from_col: from % 8,
+                        to_row: to / 8,
+                        to_col: to % 8,
+                        promotion,
+                    };
+                    if board.is_legal(&m) {
+                        moves.push(m);
+                    }
+                }
+            }
+        }
+        moves
+    }
+
+    #[test]
+    fn generated_moves_match_brute_force() {
+        let positions = [
+            START_FEN,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        ];
+        let mut rng = 0x2545_F491_4F6C_DD1Du64;
+        for fen in positions {
+            for _ in 0..6 {
+                let mut board = Board::from_fen(fen);
+                for _ in 0..60 {
+                    let moves = generate_moves(&board);
+                    assert_eq!(
+                        coords(&moves),
+                        coords(&brute_force_moves(&board)),
+                        "{}",
+                        board.to_fen()
+                    );
+                    if moves.is_empty() {
+                        break;
+                    }
+                    rng = splitmix64(rng).0;
+                    board.make_move(&moves[(splitmix64(rng).1 % moves.len() as u64) as usize]);
+                }
+            }
+        }
+    }
+
+    // Mate scores
+
+    #[test]
+    fn prefers_the_quickest_mate() {
+        // Qg7# mates at once, and a five-ply search also sees the queen's
+        // slower mates in two and three
+        let fen = "7k/8/5K2/8/8/8/8/6Q1 w - - 0 1";
+        let config = SearchConfig {
+            depth: 5,
+            use_book: false,
+            ..SearchConfig::default()
+        };
+        let result = search(fen, &config);
+        assert_eq!(result.score, EvalReport::Mate(1));
+        let mut board = Board::from_fen(fen);
+        board.make_move(&result.best_move.unwrap());
+        assert_eq!(board.status(), GameStatus::Checkmate);
+    }
+
+    #[test]
+    fn mate_scores_round_trip_through_the_tt() {
+        // A mate seven plies from the root, stored at ply 3, is four plies
+        // from that position, and nine from the root when met at ply 5
+        let win = -MATE_SCORE - 7;
+        assert_eq!(score_to_tt(win, 3), -MATE_SCORE - 4);
+        assert_eq!(score_from_tt(score_to_tt(win, 3), 3), win);
+        assert_eq!(score_from_tt(score_to_tt(win, 3), 5), -MATE_SCORE - 9);
+        let loss = MATE_SCORE + 6;
+        assert_eq!(score_from_tt(score_to_tt(loss, 2), 2), loss);
+        assert_eq!(score_from_tt(score_to_tt(loss, 2), 4), MATE_SCORE + 8);
+        assert_eq!(score_from_tt(score_to_tt(150, 9), 1), 150);
+
+        // A second search reads the first one's mates back from the table
+        let mut engine = Engine::new(Board::from_fen("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1"));
+        engine.use_book = false;
+        for _ in 0..2 {
+            let m = engine.search(4, &[]).unwrap();
+            assert_eq!(m.to_coords(), "d1d8");
+            assert_eq!(engine.score, Some(EvalReport::Mate(1)));
+        }
+    }
+
+    // Incrementally updated keys and material
+
+    /// The placement key, pawn key and material computed from the
+    /// bitboards alone.
+    fn keys_from_scratch(board: &Board) -> (u64, u64, i32) {
+        let (mut placement, mut pawns, mut material) = (0, 0, 0);
+        for color in [Color::White, Color::Black] {
+            for piece_type in PieceType::ALL {
+                for sq in BitIter(board.bitboard(color, piece_type)) {
+                    let key = ZOBRIST_PIECES[color.index()][piece_type.index()][sq];
+                    placement ^= key;
+                    if piece_type == PieceType::Pawn {
+                        pawns ^= key;
+                    }
+                    material += match color {
+                        Color::White => piece_type.value(),
+                        Color::Black => -piece_type.value(),
+                    };
+                }
+            }
+        }
+        (placement, pawns, material)
+    }
+
+    #[test]
+    fn incremental_keys_match_recomputation() {
+        let positions = [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        ];
+        let (mut castles, mut en_passants, mut promotions) = (0, 0, 0);
+        let mut rng = 0x0123_4567_89AB_CDEFu64;
+        for fen in positions {
+            for _ in 0..25 {
+                let mut board = Board::from_fen(fen);
+                for _ in 0..100 {
+                    let moves = generate_moves(&board);
+                    if moves.is_empty() {
+                        break;
+                    }
+                    rng = splitmix64(rng).0;
+                    // Random play seldom takes en passant, so it always does
+                    // when it can
+                    let m = moves
+                        .iter()
+                        .find(|m| board.is_en_passant(m))
+                        .unwrap_or(&moves[(splitmix64(rng).1 % moves.len() as u64) as usize]);
+                    castles += board.is_castling(m) as u32;
+                    en_passants += board.is_en_passant(m) as u32;
+                    promotions += m.promotion.is_some() as u32;
+                    board.make_move(m);
+
+                    let fresh = Board::from_fen(&board.to_fen());
+                    assert_eq!(
+                        (board.placement_key, board.pawn_key, board.material),
+                        keys_from_scratch(&board),
+                        "{}",
+                        board.to_fen()
+                    );
+                    assert_eq!(
+                        board.zobrist_key(),
+                        fresh.zobrist_key(),
+                        "{}",
+                        board.to_fen()
+                    );
+                }
+            }
+        }
+        assert!(castles > 0 && en_passants > 0 && promotions > 0);
+    }
+
+    // En passant and position identity
+
+    #[test]
+    fn phantom_en_passant_does_not_change_the_hash() {
+        // After 1. e4 d5 no white pawn can take on d6
+        let pushed = play(START_FEN, &["e2e4", "d7d5"]);
+        // The same position with d5 played in two steps, the bishop's
+        // round trip giving White back the tempo
+        let transposed = play(
+            START_FEN,
+            &[
+                "e2e4", "d7d6", "f1e2", "g8f6", "e2d3", "f6g8", "d3f1", "d6d5",
+            ],
+        );
+        assert_eq!(pushed.hash(), transposed.hash());
+        let phantom =
+            Board::from_fen("rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 2");
+        assert_eq!(phantom.hash(), pushed.hash());
+
+        // A real capture does count
+        let real = play(
+            "rnbqkbnr/pppppppp/8/4P3/8/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1",
+            &["d7d5"],
+        );
+        let without =
+            Board::from_fen("rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2");
+        assert_ne!(real.hash(), without.hash());
+    }
+
+    #[test]
+    fn pinned_en_passant_does_not_change_the_hash() {
+        // Taking on c6 would leave the king on a5 to the rook on h5
+        let pushed = play("4k3/2p5/8/KP5r/8/8/8/8 b - - 0 1", &["c7c5"]);
+        assert!(!generate_moves(&pushed)
+            .iter()
+            .any(|m| pushed.is_en_passant(m)));
+        let without = Board::from_fen("4k3/8/8/KPp4r/8/8/8/8 w - - 0 2");
+        assert_eq!(pushed.hash(), without.hash());
+    }
+
+    #[test]
+    fn fen_omits_phantom_en_passant() {
+        let phantom =
+            Board::from_fen("rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 2");
+        assert_eq!(
+            phantom.to_fen(),
+            "rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 1"
+        );
+        let pinned = play("4k3/2p5/8/KP5r/8/8/8/8 b - - 0 1", &["c7c5"]);
+        assert_eq!(pinned.to_fen().split(' ').nth(3), Some("-"));
+        let real = play(
+            "rnbqkbnr/pppppppp/8/4P3/8/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1",
+            &["d7d5"],
+        );
+        assert_eq!(real.to_fen().split(' ').nth(3), Some("d6"));
+    }
+
+    // Repetition
+
+    #[test]
+    fn repeating_a_game_position_scores_as_a_draw() {
+        // Black is a queen down, but Kg8 returns to a position already
+        // played in the game
+        let board = Board::from_fen("7k/8/8/8/8/8/8/K2Q4 b - - 0 1");
+        let earlier = play("7k/8/8/8/8/8/8/K2Q4 b - - 0 1", &["h8g8"]);
+
+        let mut engine = Engine::new(board.clone());
+        engine.use_book = false;
+        engine.search(4, &[]);
+        assert!(matches!(engine.score, Some(EvalReport::Cp(cp)) if cp < -500));
+
+        let mut engine = Engine::new(board);
+        engine.use_book = false;
+        engine.set_game_history(&[earlier]);
+        let m = engine.search(4, &[]).unwrap();
+        assert_eq!(m.to_coords(), "h8g8");
+        assert_eq!(engine.score, Some(EvalReport::Cp(0)));
+    }
+
+    #[test]
+    fn repeating_a_position_on_the_search_path_scores_as_a_draw() {
+        // Two rooks down, White checks from c8 and f5 until the position
+        // after the first check comes round again inside the search
+        let result = search(
+            "7k/5pp1/7p/5Q2/8/8/rr3PPP/6K1 w - - 0 1",
+            &SearchConfig {
+                depth: 4,
+                use_book: false,
+                hash_mb: 1,
+                ..SearchConfig::default()
+            },
+        );
+        assert_eq!(result.score, EvalReport::Cp(0));
+    }
+
+    #[test]
+    fn losing_side_heads_for_perpetual_check() {
+        let config = SearchConfig {
+            depth: 6,
+            use_book: false,
+            hash_mb: 1,
+            ..SearchConfig::default()
+        };
+        for (fen, check) in [
+            ("6k1/6p1/7p/5Q2/8/8/rr3PPP/6K1 w - - 0 1", "f5c8"),
+            ("7k/5pp1/7p/5Q2/8/8/rr3PPP/6K1 w - - 0 1", "f5c8"),
+            ("6k1/6p1/7p/8/8/8/rr3PPP/2Q3K1 w - - 0 1", "c1c8"),
+        ] {
+            let result = search(fen, &config);
+            assert_eq!(result.best_move.unwrap().to_coords(), check, "{}", fen);
+            assert_eq!(result.score, EvalReport::Cp(0), "{}", fen);
+        }
+    }
+
+    // Search for both colours
+
+    #[test]
+    fn finds_mate_in_one_for_either_side() {
+        for (fen, mates) in [
+            ("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1", &["d1d8"][..]),
+            ("3r2k1/5ppp/8/8/8/8/5PPP/6K1 b - - 0 1", &["d8d1"]),
+            ("6k1/8/8/8/6n1/8/6PP/6RK b - - 0 1", &["g4f2"]),
+            ("r7/8/8/8/8/4k3/8/4K3 b - - 0 1", &["a8a1"]),
+            ("6k1/1b4q1/8/8/8/8/6PP/7K b - - 0 1", &["g7g2", "g7a1"]),
+        ] {
+            let m = get_best_move_core(fen, 3, &[]).unwrap();
+            assert!(
+                mates.contains(&m.to_coords().as_str()),
+                "{} played {}",
+                fen,
+                m.to_coords()
+            );
+        }
+    }
+
+    #[test]
+    fn wins_material_for_either_side() {
+        for (fen, capture) in [
+            ("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1", "d2d5"),
+            ("4k3/3r4/8/8/3Q4/8/8/4K3 b - - 0 1", "d7d4"),
+            ("4k3/8/8/2r1q3/3P4/8/8/4K3 w - - 0 1", "d4e5"),
+            ("4k3/8/8/3p4/2R1Q3/8/8/4K3 b - - 0 1", "d5e4"),
+        ] {
+            let m = get_best_move_core(fen, 3, &[]).unwrap();
+            assert_eq!(m.to_coords(), capture, "{}", fen);
+        }
+    }
+
+    // Search features measured against the search without them
+
+    /// A quiet middlegame where each pruning and ordering feature saves
+    /// nodes at depth 5 without changing the move.
+    const QUIET_MIDDLEGAME: &str =
+        "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10";
+
+    /// The move, score and node count of a fixed-depth search of `fen`
+    /// after `configure` has adjusted the engine.
+    fn search_with(
+        fen: &str,
+        depth: u8,
+        configure: impl FnOnce(&mut Engine),
+    ) -> (String, Option<EvalReport>, u64) {
+        let mut engine = Engine::new(Board::from_fen(fen));
+        engine.use_book = false;
+        engine.hash_mb = 1;
+        configure(&mut engine);
+        let m = engine.search(depth, &[]).unwrap();
+        (m.to_coords(), engine.score, engine.nodes)
+    }
+
+    /// Asserts that turning a feature off keeps the move and costs nodes.
+    fn assert_saves_nodes(fen: &str, depth: u8, turn_off: impl FnOnce(&mut Engine)) {
+        let (with_move, with_score, with_nodes) = search_with(fen, depth, |_| {});
+        let (without_move, without_score, without_nodes) = search_with(fen, depth, turn_off);
+        assert_eq!(with_move, without_move);
+        assert_eq!(with_score, without_score);
+        assert!(
+            with_nodes < without_nodes,
+            "{} nodes with, {} without",
+            with_nodes,
+            without_nodes
+        );
+    }
+
+    #[test]
+    fn killers_and_history_save_nodes() {
+        assert_saves_nodes(QUIET_MIDDLEGAME, 5, |e| e.use_killers = false);
+    }
+
+    #[test]
+    fn aspiration_windows_save_nodes() {
+        assert_saves_nodes(QUIET_MIDDLEGAME, 5, |e| e.use_aspiration = false);
+    }
+
+    #[test]
+    fn late_move_reductions_save_nodes() {
+        assert_saves_nodes(QUIET_MIDDLEGAME, 5, |e| e.use_lmr = false);
+    }
+
+    #[test]
+    fn futility_pruning_saves_nodes() {
+        assert_saves_nodes(QUIET_MIDDLEGAME, 5, |e| e.use_futility = false);
+    }
+
+    #[test]
+    fn futility_pruning_keeps_winning_captures() {
+        // The rook takes the queen whatever the margin: captures are
+        // never futility-pruned.
+        let fen = "4k3/8/8/3q4/8/8/8/3RK3 w - - 0 1";
+        for depth in 1..=4 {
+            let (with, _, _) = search_with(fen, depth, |_| {});
+            let (without, _, _) = search_with(fen, depth, |e| e.use_futility = false);
+            assert_eq!(with, "d1d5", "depth {}", depth);
+            assert_eq!(without, "d1d5", "depth {}", depth);
+        }
+    }
+
+    #[test]
+    fn delta_pruning_saves_nodes() {
+        assert_saves_nodes(QUIET_MIDDLEGAME, 5, |e| e.use_delta_pruning = false);
+    }
+
+    #[test]
+    fn delta_pruning_keeps_the_tactical_score() {
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4",
+            "r2q1rk1/ppp2ppp/2np1n2/2b1p1B1/2B1P1b1/2NP1N2/PPP2PPP/R2Q1RK1 w - - 0 8",
+        ] {
+            let (with_move, with_score, _) = search_with(fen, 4, |_| {});
+            let (without_move, without_score, _) =
+                search_with(fen, 4, |e| e.use_delta_pruning = false);
+            assert_eq!(with_move, without_move, "{}", fen);
+            assert_eq!(with_score, without_score, "{}", fen);
+        }
+    }
+
+    #[test]
+    fn pawn_hash_leaves_the_search_unchanged() {
+        // The cached pawn score is exact, so the search visits the same
+        // tree with or without it.
+        for fen in [
+            QUIET_MIDDLEGAME,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        ] {
+            for params in [EvalParams::default(), EvalParams::positional()] {
+                let with = search_with(fen, 4, |e| e.params = params.clone());
+                let without = search_with(fen, 4, |e| {
+                    e.params = params.clone();
+                    e.use_pawn_hash = false;
+                });
+                assert_eq!(with, without, "{}", fen);
+            }
+        }
+    }
+
+    #[test]
+    fn pawn_hash_matches_a_fresh_score() {
+        let mut table = PawnHashTable::new();
+        for params in [EvalParams::default(), EvalParams::positional()] {
+            for fen in [
+                START_FEN,
+                // Doubled c-pawns and an isolated h-pawn for White
+                "4k3/pp3ppp/8/8/8/2P5/P1P4P/4K3 w - - 0 1",
+                "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            ] {
+                let board = Board::from_fen(fen);
+                let fresh = pawn_score(&board, &params);
+                // The first call fills the slot, the second reads it back
+                assert_eq!(table.score(&board, &params), fresh, "{}", fen);
+                assert_eq!(table.score(&board, &params), fresh, "{}", fen);
+            }
+        }
+        let board = Board::from_fen("4k3/pp3ppp/8/8/8/2P5/P1P4P/4K3 w - - 0 1");
+        assert!(table.score(&board, &EvalParams::default()) < 0);
+    }
+
+    #[test]
+    fn check_extensions_find_a_mate_past_the_horizon() {
+        // Philidor's smothered mate: Nf7+ Kg8 Nh6+ Kh8 Qg8+ Rxg8 Nf7# is
+        // seven plies, out of reach of a depth 5 search unless the checks
+        // are extended.
+        let fen = "r6k/6pp/8/6N1/8/1Q6/6PP/6K1 w - - 0 1";
+        let (with_move, with_score, _) = search_with(fen, 5, |_| {});
+        assert_eq!(with_move, "g5f7");
+        assert_eq!(with_score, Some(EvalReport::Mate(4)));
+        let (_, without_score, _) = search_with(fen, 5, |e| e.use_check_extensions = false);
+        assert!(matches!(without_score, Some(EvalReport::Cp(_))));
+    }
+
+    // Check evasions
+
+    #[test]
+    fn double_check_allows_only_king_moves() {
+        // The rook and knight both give check, so neither capturing the
+        // knight (Rxd3) nor blocking the file (Ne2) answers both
+        let board = Board::from_fen("k3r3/8/8/8/8/3n3R/8/4K1N1 w - - 0 1");
+        assert_eq!(coords(&generate_moves(&board)), ["e1d1", "e1d2", "e1f1"]);
+    }
+
+    #[test]
+    fn single_check_allows_captures_and_blocks() {
+        let board = Board::from_fen("k3r3/8/8/1B6/8/7R/8/4K1N1 w - - 0 1");
+        assert_eq!(
+            coords(&generate_moves(&board)),
+            ["b5e2", "b5e8", "e1d1", "e1d2", "e1f1", "e1f2", "g1e2", "h3e3"]
+        );
+    }
+
+    // Search limits and reporting
+
+    #[test]
+    fn node_limit_is_never_exceeded() {
+        for max_nodes in [1_000, 5_000, 20_000] {
+            let config = SearchConfig {
+                depth: u8::MAX,
+                max_nodes: Some(max_nodes),
+                use_book: false,
+                ..SearchConfig::default()
+            };
+            let result = search(QUIET_MIDDLEGAME, &config);
+            assert!(result.best_move.is_some());
+            assert!(
+                result.nodes <= max_nodes,
+                "{} nodes for a limit of {}",
+                result.nodes,
+                max_nodes
+            );
+        }
+    }
+
+    #[test]
+    fn search_reports_the_depth_reached_and_nodes() {
+        for depth in 1..=4 {
+            let config = SearchConfig {
+                depth,
+                use_book: false,
+                ..SearchConfig::default()
+            };
+            let result = search(START_FEN, &config);
+            assert_eq!(result.depth, depth);
+            assert!(result.nodes > 0);
+        }
+    }
+
+    #[test]
+    fn streaming_reports_each_iteration_and_ends_on_the_search_result() {
+        let config = SearchConfig {
+            depth: 5,
+            use_book: false,
+            ..SearchConfig::default()
+        };
+        let mut iterations = Vec::new();
+        search_streaming(QUIET_MIDDLEGAME, &config, |result| {
+            iterations.push(result.clone())
+        });
+        let depths: Vec<u8> = iterations.iter().map(|r| r.depth).collect();
+        assert_eq!(depths, [1, 2, 3, 4, 5]);
+
+        let last = iterations.pop().unwrap();
+        let searched = search(QUIET_MIDDLEGAME, &config);
+        assert_eq!(last.best_move, searched.best_move);
+        assert_eq!(last.score, searched.score);
+        assert_eq!(last.pv, searched.pv);
+        assert_eq!(last.nodes, searched.nodes);
+    }
+
+    #[test]
+    fn parallel_search_agrees_with_serial() {
+        for fen in [
+            "r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4",
+            "r6k/6pp/8/6N1/8/1Q6/6PP/6K1 w - - 0 1",
+            "4k3/8/8/3q4/8/8/8/3RK3 w - - 0 1",
+        ] {
+            let config = SearchConfig {
+                depth: 5,
+                use_book: false,
+                ..SearchConfig::default()
+            };
+            let serial = search(fen, &config).best_move;
+            for threads in [2, 4] {
+                assert_eq!(
+                    get_best_move_parallel(fen, 5, threads),
+                    serial,
+                    "{} with {} threads",
+                    fen,
+                    threads
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn excluding_every_move_finds_nothing() {
+        let board = Board::from_fen(START_FEN);
+        let mut engine = Engine::new(board.clone());
+        assert_eq!(engine.search(3, &generate_moves(&board)), None);
+        assert_eq!(engine.score, None);
+
+        let config = SearchConfig {
+            depth: 3,
+            excluded: generate_moves(&board),
+            ..SearchConfig::default()
+        };
+        let result = search(START_FEN, &config);
+        assert_eq!(result.best_move, None);
+        assert_eq!(result.score, EvalReport::Cp(0));
+        assert_eq!(result.depth, 0);
+    }
+
+    #[test]
+    fn search_config_sets_the_depth_and_excluded_moves() {
+        // Taking the queen is the only sensible move until it is excluded
+        let fen = "4k3/8/8/3q4/8/8/8/3RK3 w - - 0 1";
+        let excluded = vec![Move::from_coords("d1d5").unwrap()];
+        let config = SearchConfig {
+            depth: 3,
+            use_book: false,
+            excluded: excluded.clone(),
+            ..SearchConfig::default()
+        };
+        let result = search(fen, &config);
+        assert_eq!(result.depth, 3);
+        let best = result.best_move.unwrap();
+        assert!(!excluded.contains(&best));
+        assert!(matches!(result.score, EvalReport::Cp(cp) if cp < 0));
+    }
+
+    // Game status
+
+    #[test]
+    fn game_status_names_each_outcome() {
+        for (fen, status) in [
+            (START_FEN, "ongoing"),
+            (
+                "rnbqkbnr/ppppp2p/5p2/6p1/4P3/8/PPPP1PPP/RNBQKBNR w KQkq g6 0 3",
+                "ongoing",
+            ),
+            (
+                "rnbqkbnr/ppppp2p/5p2/6pQ/4P3/8/PPPP1PPP/RNB1KBNR b KQkq - 1 3",
+                "checkmate",
+            ),
+            ("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1", "stalemate"),
+            ("4k3/8/8/8/8/8/4P3/4K2R w - - 100 80", "fifty_move_draw"),
+            ("4k3/8/8/8/8/8/8/2B1K3 w - - 0 1", "insufficient_material"),
+            ("4k3/8/8/8/8/8/8/4K1N1 w - - 0 1", "insufficient_material"),
+            ("2b1k3/8/8/8/8/8/8/4KB2 w - - 0 1", "insufficient_material"),
+            ("4k3/8/8/8/8/8/8/4KN1N w - - 0 1", "ongoing"),
+            ("4k3/4r3/8/8/8/8/8/4K3 w - - 0 1", "in_check"),
+        ] {
+            assert_eq!(game_status_core(fen).as_str(), status, "{}", fen);
+        }
+    }
+
+    #[test]
+    fn checkmate_takes_precedence_over_the_fifty_move_rule() {
+        let fen = "rnbqkbnr/ppppp2p/5p2/6pQ/4P3/8/PPPP1PPP/RNB1KBNR b KQkq - 100 60";
+        assert_eq!(game_status_core(fen), GameStatus::Checkmate);
+    }
+
+    #[test]
+    fn no_move_is_found_at_a_terminal_position() {
+        // Back-rank mate, then the queen and king stalemating a lone king
+        for (fen, status) in [
+            ("3R2k1/5ppp/8/8/8/8/8/6K1 b - - 0 1", GameStatus::Checkmate),
+            ("k7/2Q5/1K6/8/8/8/8/8 b - - 0 1", GameStatus::Stalemate),
+        ] {
+            assert_eq!(get_best_move_core(fen, 3, &[]), None, "{}", fen);
+            assert_eq!(game_status_core(fen), status, "{}", fen);
+        }
+    }
+
+    #[test]
+    fn winning_side_mates_rather_than_stalemates() {
+        // Qc8# and the stalemating Qc7 are both one move away
+        let fen = "k7/8/1K6/8/2Q5/8/8/8 w - - 0 1";
+        assert_eq!(play(fen, &["c4c7"]).status(), GameStatus::Stalemate);
+        for depth in 1..=3 {
+            let (best, score, _) = search_with(fen, depth, |_| {});
+            assert_eq!(best, "c4c8", "depth {}", depth);
+            assert_eq!(score, Some(EvalReport::Mate(1)), "depth {}", depth);
+        }
+    }
+
+    #[test]
+    fn losing_side_sacrifices_into_stalemate() {
+        // Rf1+ Kxf1 leaves the white king without a move
+        let fen = "q7/8/8/8/8/4ppp1/4nk2/1R5K w - - 0 1";
+        assert_eq!(play(fen, &["b1f1", "f2f1"]).status(), GameStatus::Stalemate);
+        for depth in 2..=4 {
+            let (best, score, _) = search_with(fen, depth, |_| {});
+            assert_eq!(best, "b1f1", "depth {}", depth);
+            assert_eq!(score, Some(EvalReport::Cp(0)), "depth {}", depth);
+        }
+    }
+
+    // Endgame knowledge
+
+    /// Plays the engine against itself from `fen` at `depth` until the game
+    /// ends or `max_plies` have been played.
+    fn play_out(fen: &str, depth: u8, max_plies: usize) -> Board {
+        let mut board = Board::from_fen(fen);
+        for _ in 0..max_plies {
+            if board.status().is_game_over() {
+                break;
+            }
+            let m = search_with(&board.to_fen(), depth, |_| {}).0;
+            board.make_move(&Move::from_coords(&m).unwrap());
+        }
+        board
+    }
+
+    #[test]
+    fn queen_and_rook_mate_a_lone_king() {
+        for (fen, depth, max_plies) in [
+            ("8/8/8/4k3/8/8/8/3QK3 w - - 0 1", 3, 40),
+            ("4k3/8/8/8/8/8/8/1Q5K w - - 0 1", 3, 40),
+            ("8/8/3k4/8/8/8/8/R3K3 w - - 0 1", 4, 60),
+        ] {
+            let board = play_out(fen, depth, max_plies);
+            assert_eq!(board.status(), GameStatus::Checkmate, "{}", fen);
+            assert_eq!(board.turn, Color::Black, "{}", fen);
+        }
+    }
+
+    // Contempt
+
+    #[test]
+    fn contempt_steers_away_from_a_repetition() {
+        // A pawn down, Black can return to the position after its first
+        // move by going back to g8
+        let fen = "rnbqkbnr/pppp1ppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        let line = ["g1f3", "g8f6", "f3g1"];
+        let history: Vec<Board> = (0..line.len()).map(|i| play(fen, &line[..i])).collect();
+        let board = play(fen, &line);
+        let search_with_contempt = |contempt| {
+            let mut engine = Engine::new(board.clone());
+            engine.use_book = false;
+            engine.hash_mb = 1;
+            engine.contempt = contempt;
+            engine.set_game_history(&history);
+            let m = engine.search(4, &[]).unwrap();
+            (m.to_coords(), engine.score.unwrap())
+        };
+
+        // The repetition scores `contempt` below zero for the engine's side
+        assert_eq!(
+            search_with_contempt(0),
+            ("f6g8".to_string(), EvalReport::Cp(0))
+        );
+        assert_eq!(
+            search_with_contempt(50),
+            ("f6g8".to_string(), EvalReport::Cp(-50))
+        );
+        let (m, score) = search_with_contempt(200);
+        assert_ne!(m, "f6g8");
+        assert!(matches!(score, EvalReport::Cp(cp) if cp > -200));
+    }
+
+    #[test]
+    fn king_proximity_rewards_the_side_ahead() {
+        let params = EvalParams::default();
+        let far = Board::from_fen("8/8/8/8/2k5/8/6PP/7K w - - 0 1");
+        let near = Board::from_fen("8/8/8/8/2k5/4K3/6PP/8 w - - 0 1");
+        assert!(king_proximity(&near, &params) > king_proximity(&far, &params));
+        assert!(king_proximity(&far, &params) > 0);
+        assert!(king_proximity(&far.mirrored(), &params) < 0);
+        assert_eq!(king_proximity(&Board::from_fen(START_FEN), &params), 0);
+    }
+
+    #[test]
+    fn king_proximity_brings_the_king_closer() {
+        // Two pawns up, White walks its king over only for the proximity term
+        let fen = "8/8/8/8/2k5/8/6PP/7K w - - 0 1";
+        let (with, _, _) = search_with(fen, 4, |_| {});
+        assert_eq!(with, "h1g1");
+        let (without, _, _) = search_with(fen, 4, |e| e.params.king_proximity_bonus = 0);
+        assert_ne!(without, "h1g1");
+    }
+
+    // Cached checkers
+
+    /// Asserts that `board`'s cached checkers match a fresh scan from the
+    /// king of the side to move.
+    fn assert_checkers_fresh(board: &Board) {
+        let ksq = board.bitboard(board.turn, PieceType::King).trailing_zeros() as usize;
+        let fresh = board
+            .attackers_to(ksq, board.turn.opponent())
+            .into_iter()
+            .fold(0, |acc, sq| acc | 1u64 << sq);
+        assert_eq!(board.checkers(), fresh, "{}", board.to_fen());
+        assert_eq!(
+            board.in_check(),
+            is_square_attacked(board, ksq, board.turn.opponent()),
+            "{}",
+            board.to_fen()
+        );
+        assert_eq!(
+            board.checkers(),
+            Board::from_fen(&board.to_fen()).checkers()
+        );
+    }
+
+    #[test]
+    fn cached_checkers_follow_the_moves() {
+        let fen = "4k3/8/8/8/4N3/8/8/4R1K1 w - - 0 1";
+        for line in [
+            // Discovered check from the rook, then a knight check and a
+            // rook check
+            &["e4c5", "e8d8", "c5b7", "d8c7", "e1e7", "c7b6"][..],
+            // Double check
+            &["e4f6", "e8f8", "f6h7", "f8g8", "e1e8"][..],
+        ] {
+            let mut board = Board::from_fen(fen);
+            assert_checkers_fresh(&board);
+            for coords in line {
+                let m = Move::from_coords(coords).unwrap();
+                assert!(board.is_legal(&m), "{} in {}", coords, board.to_fen());
+                board.make_move(&m);
+                assert_checkers_fresh(&board);
+                let undo = board.make_null_move();
+                assert_checkers_fresh(&board);
+                board.unmake_null_move(undo);
+                assert_checkers_fresh(&board);
+            }
+        }
+
+        let discovered = play(fen, &["e4c5"]);
+        assert_eq!(discovered.checkers(), 1 << 60);
+        let double = play(fen, &["e4f6"]);
+        assert_eq!(double.checkers().count_ones(), 2);
+    }
+
+    // Pins
+
+    fn square(name: &str) -> Square {
+        Square::from_algebraic(name).unwrap()
+    }
+
+    #[test]
+    fn knight_pinned_by_a_bishop() {
+        let board = Board::from_fen("4k3/8/8/8/1b6/8/3N4/4K3 w - - 0 1");
+        assert_eq!(
+            board.pinned_pieces(Color::White),
+            [(square("d2"), square("b4"))]
+        );
+        assert!(board.pinned_pieces(Color::Black).is_empty());
+        // The pinned knight has no legal move
+        assert!(generate_moves(&board)
+            .iter()
+            .all(|m| Square::from_row_col(m.from_row, m.from_col) != Some(square("d2"))));
+    }
+
+    #[test]
+    fn no_pins_without_a_line_to_the_king() {
+        // The bishop eyes the knight, but the king isn't behind it, and two
+        // pieces between the rook and king don't make a pin
+        for fen in [
+            START_FEN,
+            "4k3/8/8/8/1b6/8/3N4/5K2 w - - 0 1",
+            "4k3/4r3/8/8/4N3/8/4P3/4K3 w - - 0 1",
+        ] {
+            let board = Board::from_fen(fen);
+            assert!(board.pinned_pieces(Color::White).is_empty(), "{}", fen);
+            assert!(board.pinned_pieces(Color::Black).is_empty(), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn uci_moves_show_castling_as_the_king_lands() {
+        let board = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+        assert_eq!(
+            board.move_to_uci(&Move::from_coords("e1h1").unwrap()),
+            "e1g1"
+        );
+        assert_eq!(
+            board.move_to_uci(&Move::from_coords("e1a1").unwrap()),
+            "e1c1"
+        );
+        assert_eq!(
+            board.move_to_uci(&Move::from_coords("a1a8").unwrap()),
+            "a1a8"
+        );
+        // A Chess960 king already on g1 castles onto its rook and stays put
+        let board = Board::from_fen("6kr/8/8/8/8/8/8/6KR w Hh - 0 1");
+        assert_eq!(
+            board.move_to_uci(&Move::from_coords("g1h1").unwrap()),
+            "g1h1"
+        );
+        let board = Board::from_fen("8/4P2k/8/8/8/8/8/4K3 w - - 0 1");
+        assert_eq!(
+            board.move_to_uci(&Move::from_coords("e7e8n").unwrap()),
+            "e7e8n"
+        );
+    }
+
+    // Tablebases
+
+    /// Reports every position it covers as lost for the side to move,
+    /// counting the probes.
+    struct LossTablebase {
+        probes: std::sync::atomic::AtomicU64,
+    }
+
+    impl TablebaseProvider for LossTablebase {
+        fn probe(&self, _board: &Board) -> Option<TbResult> {
+            self.probes.fetch_add(1, Ordering::Relaxed);
+            Some(TbResult {
+                wdl: Wdl::Loss,
+                dtz: None,
+            })
+        }
+
+        fn max_pieces(&self) -> u32 {
+            3
+        }
+    }
+
+    #[test]
+    fn search_takes_the_tablebase_result() {
+        // Taking the queen leaves three pieces, which the tablebase scores
+        // as lost for Black without the search looking further
+        let tablebase = Arc::new(LossTablebase {
+            probes: Default::default(),
+        });
+        let mut engine = Engine::new(Board::from_fen("4k3/8/8/3q4/8/8/8/3RK3 w - - 0 1"));
+        engine.use_book = false;
+        engine.tablebase = tablebase.clone();
+        let m = engine.search(4, &[]).unwrap();
+        assert_eq!(m.to_coords(), "d1d5");
+        assert_eq!(engine.score, Some(EvalReport::Cp(TB_WIN_SCORE - 1)));
+        assert!(tablebase.probes.load(Ordering::Relaxed) > 0);
+        // The tablebase position ends the line
+        assert_eq!(engine.pv.len(), 1);
+    }
+
+    #[test]
+    fn tablebase_scores_round_trip_through_the_tt() {
+        let win = TB_WIN_SCORE - 7;
+        assert_eq!(score_from_tt(score_to_tt(win, 3), 3), win);
+        assert_eq!(score_from_tt(score_to_tt(win, 3), 5), TB_WIN_SCORE - 9);
+        let loss = -TB_WIN_SCORE + 6;
+        assert_eq!(score_from_tt(score_to_tt(loss, 2), 4), -TB_WIN_SCORE + 8);
+    }
+
+    // Legal moves for a UI
+
+    #[test]
+    fn legal_moves_from_a_square_in_uci() {
+        let mut from_g1 = legal_moves_from_core(START_FEN, "g1");
+        from_g1.sort();
+        assert_eq!(from_g1, ["g1f3", "g1h3"]);
+        assert!(legal_moves_from_core(START_FEN, "e4").is_empty());
+        assert!(legal_moves_from_core(START_FEN, "e7").is_empty());
+        assert!(legal_moves_from_core(START_FEN, "z9").is_empty());
+
+        let mut from_e1 = legal_moves_from_core("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", "e1");
+        from_e1.sort();
+        assert_eq!(
+            from_e1,
+            ["e1c1", "e1d1", "e1d2", "e1e2", "e1f1", "e1f2", "e1g1"]
+        );
+    }
+
+    #[test]
+    fn top_moves_are_in_uci() {
+        let top = top_moves_core("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", 1, usize::MAX);
+        let moves: Vec<&str> = top.iter().map(|(m, _)| m.as_str()).collect();
+        assert!(moves.contains(&"e1g1") && moves.contains(&"e1c1"));
+        assert!(!moves.contains(&"e1h1") && !moves.contains(&"e1a1"));
+    }
+
+    // Piece placement
+
+    #[test]
+    fn set_piece_round_trips_through_piece_at() {
+        let fen = "4k3/8/8/8/8/8/4Q3/3K4 b - - 0 1";
+        let mut board = Board::empty();
+        for (name, letter) in [("e8", "k"), ("e2", "Q"), ("d1", "K")] {
+            let piece: Piece = letter.parse().unwrap();
+            board.set_piece(square(name), Some(piece));
+            assert_eq!(board.piece_at(square(name)), Some(piece));
+        }
+        board.set_turn(Color::Black);
+        let expected = Board::from_fen(fen);
+        assert!(board == expected);
+        assert_eq!(board.hash(), expected.hash());
+        assert_eq!(board.material_balance(), expected.material_balance());
+        assert!(board.in_check());
+
+        board.set_piece(square("e2"), None);
+        assert_eq!(board.piece_at(square("e2")), None);
+        assert_eq!(board.material_balance(), 0);
+        assert!(!board.in_check());
+    }
+
+    // Evaluation breakdown
+
+    #[test]
+    fn breakdown_sums_to_the_evaluation() {
+        for params in [
+            EvalParams::default(),
+            EvalParams::aggressive(),
+            EvalParams::positional(),
+        ] {
+            for fen in [
+                START_FEN,
+                QUIET_MIDDLEGAME,
+                "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+                "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+                "8/8/8/4k3/8/8/8/4K2Q w - - 0 1",
+            ] {
+                let board = Board::from_fen(fen);
+                let b = evaluate_verbose(&board, &params);
+                let sum = b.material
+                    + b.bishop_pair
+                    + b.knight_pawns
+                    + b.mobility
+                    + b.pawn_structure
+                    + b.king_safety
+                    + b.rook_placement
+                    + b.king_proximity
+                    + b.basic_mate;
+                assert_eq!(sum, evaluate(&board, &params), "{}", fen);
+            }
+        }
+    }
+
+    // Promotion rows
+
+    #[test]
+    fn pawns_promote_only_on_the_far_row() {
+        let fen = "4k3/1P6/8/8/8/1P5p/6p1/4K3 w - - 0 1";
+        let white = |pt| {
+            Some(Piece {
+                piece_type: pt,
+                color: Color::White,
+            })
+        };
+        let black = |pt| {
+            Some(Piece {
+                piece_type: pt,
+                color: Color::Black,
+            })
+        };
+
+        // White promotes on row 0, rank 8
+        let board = play(fen, &["b7b8n"]);
+        assert_eq!(board.piece_on(1), white(PieceType::Knight));
+        // Black promotes on row 7, rank 1
+        let board = play(fen, &["b3b4", "g2g1r"]);
+        assert_eq!(board.piece_on(7 * 8 + 6), black(PieceType::Rook));
+
+        // Short of the far row a pawn stays a pawn
+        let board = play(fen, &["b3b4", "h3h2"]);
+        assert_eq!(board.piece_at(square("b4")), white(PieceType::Pawn));
+        assert_eq!(board.piece_at(square("h2")), black(PieceType::Pawn));
+    }
+
+    // Castling rights after a rook is captured
+
+    #[test]
+    fn capturing_the_h1_rook_ends_white_kingside_castling() {
+        let board = play("r3k2r/8/8/8/8/8/6b1/R3K2R b KQkq - 0 1", &["g2h1"]);
+        assert_eq!(board.castling_rights(), [false, true, true, true]);
+        assert!(board.to_fen().contains(" Qkq "));
+        let castles: Vec<Move> = generate_moves(&board)
+            .into_iter()
+            .filter(|m| board.is_castling(m))
+            .collect();
+        assert_eq!(coords(&castles), ["e1a1"]);
+    }
+
+    // FEN parsing errors
+
+    #[test]
+    fn malformed_fens_are_rejected() {
+        assert_eq!(
+            Board::try_from_fen("4k3/8/8/8/8/8/4K3 w - - 0 1").err(),
+            Some(PositionError::RankCount(7))
+        );
+        // Rank 6 covers nine files, then rank 3 only seven
+        assert_eq!(
+            Board::try_from_fen("4k3/8/8p/8/8/8/8/4K3 w - - 0 1").err(),
+            Some(PositionError::RankLength { rank: 6, files: 9 })
+        );
+        assert_eq!(
+            Board::try_from_fen("4k3/8/8/8/8/7/8/4K3 w - - 0 1").err(),
+            Some(PositionError::RankLength { rank: 3, files: 7 })
+        );
+    }
+
+    #[test]
+    fn validate_rejects_illegal_positions() {
+        let error = |fen| Board::from_fen(fen).validate().err();
+        assert_eq!(error(START_FEN), None);
+        assert_eq!(
+            error("8/8/8/8/8/8/8/4K3 w - - 0 1"),
+            Some(PositionError::MissingKing(Color::Black))
+        );
+        assert_eq!(
+            error("4k3/8/8/8/8/8/8/3KK3 w - - 0 1"),
+            Some(PositionError::ExtraKing(Color::White))
+        );
+        assert_eq!(
+            error("4k3/8/8/8/8/8/8/P3K3 w - - 0 1"),
+            Some(PositionError::PawnOnBackRank(square("a1")))
+        );
+        // White to move while giving check to Black
+        assert_eq!(
+            error("4k3/8/8/8/8/8/8/4R1K1 w - - 0 1"),
+            Some(PositionError::OpponentInCheck)
+        );
+    }
+
+    // Static exchange evaluation
+
+    #[test]
+    fn see_scores_captures_by_what_they_win() {
+        let qxe5 = Move::from_coords("e1e5").unwrap();
+        // The pawn on e5 is defended by d6, so the queen is lost for it
+        let defended = Board::from_fen("4k3/8/3p4/4p3/8/8/8/4QK2 w - - 0 1");
+        assert_eq!(defended.see(&qxe5), PAWN_VAL - QUEEN_VAL);
+        // Nothing guards the rook
+        let hanging = Board::from_fen("4k3/8/8/4r3/8/8/8/4QK2 w - - 0 1");
+        assert_eq!(hanging.see(&qxe5), ROOK_VAL);
+    }
+
+    // Moves that give check
+
+    #[test]
+    fn gives_check_on_a_queen_capture_but_not_a_quiet_move() {
+        let board = play(START_FEN, &["e2e4", "e7e5", "d1h5", "b8c6"]);
+        let qxf7 = Move::from_coords("h5f7").unwrap();
+        assert!(board.gives_check(&qxf7));
+        assert_eq!(board.to_san(&qxf7), "Qxf7+");
+        let nf3 = Move::from_coords("g1f3").unwrap();
+        assert!(!board.gives_check(&nf3));
+        assert_eq!(board.to_san(&nf3), "Nf3");
+    }
+
+    // Move legality
+
+    #[test]
+    fn is_legal_rejects_pin_breaking_and_empty_square_moves() {
+        // The knight on d2 is pinned to the king by the bishop on b4
+        let board = Board::from_fen("4k3/8/8/8/1b6/8/3N4/4K3 w - - 0 1");
+        let legal = |coords| board.is_legal(&Move::from_coords(coords).unwrap());
+        assert!(legal("e1f1"));
+        assert!(!legal("d2f3"));
+        assert!(!legal("e3e4"));
+    }
+
+    #[test]
+    fn move_legality_gives_each_reason() {
+        let legality =
+            |fen, coords| Board::from_fen(fen).move_legality(&Move::from_coords(coords).unwrap());
+        assert_eq!(legality(START_FEN, "e2e4"), MoveLegality::Legal);
+        assert_eq!(legality(START_FEN, "e3e4"), MoveLegality::NoPieceOnSource);
+        assert_eq!(legality(START_FEN, "e7e5"), MoveLegality::WrongColor);
+        assert_eq!(
+            legality(START_FEN, "d1d2"),
+            MoveLegality::OccupiedByOwnPiece
+        );
+        assert_eq!(legality(START_FEN, "a1a3"), MoveLegality::BlockedPath);
+        assert_eq!(
+            legality(START_FEN, "g1g3"),
+            MoveLegality::NotAPseudoLegalTarget
+        );
+        assert_eq!(
+            legality("4k3/P7/8/8/8/8/8/4K3 w - - 0 1", "a7a8"),
+            MoveLegality::InvalidPromotion
+        );
+        assert_eq!(
+            legality("r3k2r/8/8/8/8/8/8/R3K2R w - - 0 1", "e1h1"),
+            MoveLegality::CannotCastle
+        );
+        assert_eq!(
+            legality("4k3/8/8/8/1b6/8/3N4/4K3 w - - 0 1", "d2f3"),
+            MoveLegality::LeavesKingInCheck
+        );
+    }
+
+    // Null moves
+
+    #[test]
+    fn null_move_restores_en_passant_and_the_clock() {
+        let before = Board::from_fen("4k3/8/8/8/3pP3/8/8/4K3 b - e3 3 1");
+        assert_eq!(before.en_passant_square(), Some(square("e3")));
+        let mut board = before.clone();
+        let undo = board.make_null_move();
+        assert_eq!(board.side_to_move(), Color::White);
+        assert_eq!(board.en_passant_square(), None);
+        assert_eq!(board.halfmove_clock(), 4);
+        assert_ne!(board.hash(), before.hash());
+
+        board.unmake_null_move(undo);
+        assert!(board == before);
+        assert_eq!(board.en_passant_square(), Some(square("e3")));
+        assert_eq!(board.halfmove_clock(), 3);
+        assert_eq!(board.hash(), before.hash());
+    }
+
+    // Games
+
+    #[test]
+    fn undo_steps_back_to_the_start() {
+        let mut game = Game::from_pgn("1. e4 e5 2. Nf3 *").unwrap();
+        let played = ["e2e4", "e7e5", "g1f3"];
+        for ply in (0..played.len()).rev() {
+            let m = game.undo().unwrap();
+            assert_eq!(m.to_coords(), played[ply]);
+            assert!(game.board == play(START_FEN, &played[..ply]));
+            let to_move = if ply % 2 == 0 {
+                Color::White
+            } else {
+                Color::Black
+            };
+            assert_eq!(game.board.side_to_move(), to_move);
+        }
+        assert!(game.undo().is_none());
+        assert!(game.board == game.start);
+    }
+
+    #[test]
+    fn from_pgn_plays_the_main_line() {
+        let pgn = r#"[Event "Club match"]
+[White "Anderssen"]
+[Black "Dufresne"]
+[Result "*"]
+
+1. e4 c5 {the Sicilian} 2. Nf3 d6 (2... Nc6 3. d4) 3. d4 cxd4 4. Nxd4 $1 *
+"#;
+        let game = Game::from_pgn(pgn).unwrap();
+        assert_eq!(game.tags[1], ("White".to_string(), "Anderssen".to_string()));
+        assert_eq!(game.moves.len(), 7);
+        assert_eq!(
+            game.board.to_fen(),
+            "rnbqkbnr/pp2pppp/3p4/8/3NP3/8/PPP2PPP/RNBQKB1R b KQkq - 0 1"
+        );
+    }
+
+    #[test]
+    fn top_moves_rank_the_winning_capture_first() {
+        // The rook on e5 is loose and the queen takes it
+        let top = top_moves_core("4k3/8/8/4r3/8/8/3P4/4QK2 w - - 0 1", 3, 3);
+        assert_eq!(top.len(), 3);
+        assert_eq!(top[0].0, "e1e5");
+        assert!(top[0].1 >= ROOK_VAL / 2, "{:?}", top);
+        assert!(top.windows(2).all(|w| w[0].1 >= w[1].1), "{:?}", top);
+        assert!(top[0].1 > top[1].1, "{:?}", top);
+    }
+
+    // Reported scores
+
+    #[test]
+    fn reports_mate_in_moves_and_quiet_positions_in_centipawns() {
+        // 1. Kb6 Kb8 2. Rh8#
+        assert_eq!(
+            evaluate_position_core("k7/8/2K5/8/8/8/8/7R w - - 0 1", 5),
+            EvalReport::Mate(2)
+        );
+        match evaluate_position_core(QUIET_MIDDLEGAME, 3) {
+            EvalReport::Cp(cp) => assert!(cp.abs() < 100, "{}", cp),
+            report => panic!("expected a centipawn score, got {:?}", report),
+        }
+    }
+
+    // Transposition table size
+
+    #[test]
+    fn transposition_table_stays_within_its_budget() {
+        const MB: usize = 1024 * 1024;
+        for megabytes in [1, 3, 16] {
+            let mut tt = TranspositionTable::new(megabytes);
+            assert!(tt.len().is_power_of_two());
+            assert!(tt.size_bytes() <= megabytes * MB);
+            // Twice as many slots would no longer fit
+            assert!(2 * tt.size_bytes() > megabytes * MB);
+            let slots = tt.len();
+            for key in 0..4 * slots as u64 {
+                tt.store(
+                    key.wrapping_mul(0x9E37_79B9_7F4A_7C15),
+                    1,
+                    0,
+                    Bound::Exact,
+                    None,
+                );
+            }
+            assert_eq!(tt.len(), slots);
+        }
+        assert_eq!(TranspositionTable::new(0).len(), 1);
+
+        let mut engine = Engine::new(Board::from_fen(QUIET_MIDDLEGAME));
+        engine.use_book = false;
+        engine.hash_mb = 1;
+        engine.search(4, &[]);
+        assert!(engine.tt.size_bytes() <= MB);
+    }
+
+    // Seeded move choice
+
+    #[test]
+    fn seeded_moves_repeat_for_a_seed_and_vary_across_seeds() {
+        // Kings and pawns only, so several pawn moves score about the same
+        let fen = "4k3/pppppppp/8/8/8/8/PPPPPPPP/4K3 w - - 0 1";
+        let mut played = std::collections::HashSet::new();
+        for seed in 0..16 {
+            let m = get_best_move_seeded_core(fen, 2, seed).unwrap();
+            assert_eq!(get_best_move_seeded_core(fen, 2, seed), Some(m.clone()));
+            played.insert(m.to_coords());
+        }
+        assert!(played.len() > 1, "{:?}", played);
+    }
+
+    // Opening book
+
+    #[test]
+    fn book_move_is_played_without_searching() {
+        let result = search(START_FEN, &SearchConfig::default());
+        let m = result.best_move.unwrap();
+        assert!(book_moves(&Board::from_fen(START_FEN)).contains(&m));
+        assert_eq!(result.nodes, 0);
+        assert_eq!(result.depth, 0);
+        assert_eq!(result.score, EvalReport::Cp(0));
+    }
+
+    // Fifty-move rule
+
+    #[test]
+    fn no_move_once_the_fifty_move_rule_has_drawn() {
+        let drawn = "4k3/8/8/8/8/8/4P3/R3K3 w - - 100 1";
+        assert_eq!(Board::from_fen(drawn).status(), GameStatus::FiftyMoveDraw);
+        assert_eq!(get_best_move_core(drawn, 3, &[]), None);
+        // One half-move short of it the game goes on
+        let not_yet = "4k3/8/8/8/8/8/4P3/R3K3 w - - 99 1";
+        assert!(get_best_move_core(not_yet, 3, &[]).is_some());
+    }
+
+    // Capture generation
+
+    #[test]
+    fn captures_leave_out_quiet_promotions() {
+        // g7-g8 promotes without capturing, so it isn't a capture
+        let board = Board::from_fen("k7/6P1/8/3p1p1p/4P3/8/8/4K2R w - - 0 1");
+        assert_eq!(coords(&generate_captures(&board)), ["e4d5", "e4f5", "h1h5"]);
+        assert!(coords(&generate_moves(&board)).contains(&"g7g8q".to_string()));
+    }
+
+    #[test]
+    fn move_generation_handles_boards_without_kings() {
+        assert!(generate_moves(&Board::empty()).is_empty());
+        assert!(generate_captures(&Board::empty()).is_empty());
+        for fen in [
+            "8/8/8/8/8/8/4P3/8 w - - 0 1",
+            "8/8/8/3q4/8/8/8/R7 w - - 0 1",
+            "4k3/8/8/8/8/8/4P3/8 w - - 0 1",
+            "8/8/8/8/8/8/8/4K3 b - - 0 1",
+        ] {
+            let board = Board::from_fen(fen);
+            // A side without a king has no legal moves
+            assert!(generate_moves(&board).is_empty(), "{}", fen);
+            assert!(generate_captures(&board).is_empty(), "{}", fen);
+        }
+    }
+
+    // Attacks
+
+    #[test]
+    fn attacked_squares_from_the_start() {
+        let board = Board::from_fen(START_FEN);
+        let names = |by| {
+            let mut names: Vec<String> = board
+                .attacked_squares(by)
+                .iter()
+                .map(|sq| sq.to_algebraic())
+                .collect();
+            names.sort();
+            names
+        };
+        // Every square on ranks 2 and 3, and rank 1 bar the corners,
+        // which nothing reaches
+        let mut expected: Vec<String> = ["b1", "c1", "d1", "e1", "f1", "g1"]
+            .iter()
+            .map(|s| s.to_string())
+            .chain(
+                "abcdefgh"
+                    .chars()
+                    .flat_map(|f| [format!("{}2", f), format!("{}3", f)]),
+            )
+            .collect();
+        expected.sort();
+        assert_eq!(names(Color::White), expected);
+        assert_eq!(names(Color::Black).len(), 22);
+        assert!(!names(Color::Black).contains(&"a8".to_string()));
+        assert!(names(Color::Black).contains(&"h6".to_string()));
+    }
+
+    #[test]
+    fn attackers_to_a_contested_square() {
+        let board = play(START_FEN, &["e2e4", "e7e5", "g1f3", "b8c6", "d2d4"]);
+        let attackers = |name, by| {
+            let mut names: Vec<String> = board
+                .attackers_to(square(name).index(), by)
+                .into_iter()
+                .map(|sq| Square(sq as u8).to_algebraic())
+                .collect();
+            names.sort();
+            names
+        };
+        assert_eq!(attackers("e5", Color::White), ["d4", "f3"]);
+        assert_eq!(attackers("e5", Color::Black), ["c6"]);
+        assert_eq!(attackers("d4", Color::White), ["d1", "f3"]);
+        assert_eq!(attackers("d4", Color::Black), ["c6", "e5"]);
+    }
+
+    // Position hashing
+
+    #[test]
+    fn equal_positions_hash_equal() {
+        // The clocks aren't part of the position
+        let fen = "r1bqkb1r/pppp1ppp/2n2n2/4p3/4P3/2N2N2/PPPP1PPP/R1BQKB1R w KQkq -";
+        let a = Board::from_fen(&format!("{} 4 4", fen));
+        let b = Board::from_fen(&format!("{} 0 1", fen));
+        assert_eq!(a.hash(), b.hash());
+        // Reached by a different move order
+        let moved = play(START_FEN, &["b1c3", "g8f6", "g1f3", "b8c6", "e2e4", "e7e5"]);
+        assert_eq!(moved.hash(), a.hash());
+
+        let black_to_move = Board::from_fen(&fen.replace(" w ", " b "));
+        assert_ne!(black_to_move.hash(), a.hash());
+        let no_castling = Board::from_fen(&fen.replace("KQkq", "Qkq"));
+        assert_ne!(no_castling.hash(), a.hash());
+        let knight_back = play(
+            START_FEN,
+            &["b1c3", "g8f6", "g1f3", "b8c6", "e2e4", "e7e5", "c3b1"],
+        );
+        assert_ne!(knight_back.hash(), a.hash());
+    }
+
+    #[test]
+    fn en_passant_rights_set_positions_apart() {
+        // The same placement, but only the first lets d4 take on e3
+        let with_ep = Board::from_fen("4k3/8/8/8/3pP3/8/8/4K3 b - e3 0 1");
+        let without = Board::from_fen("4k3/8/8/8/3pP3/8/8/4K3 b - - 0 1");
+        assert!(with_ep != without);
+        assert_ne!(with_ep.hash(), without.hash());
+        assert_eq!(
+            without.status_with_history(&[with_ep.clone(), with_ep]),
+            GameStatus::Ongoing
+        );
+        assert_eq!(
+            without.status_with_history(&[without.clone(), without.clone()]),
+            GameStatus::ThreefoldRepetition
+        );
+    }
+
+    // Material signatures
+
+    #[test]
+    fn material_signature_of_kqk_and_with_a_pawn() {
+        let kqk = Board::from_fen("4k3/8/8/8/8/8/8/3QK3 w - - 0 1").material_signature();
+        assert_eq!(kqk.to_string(), "KQvK");
+        assert_eq!(kqk.count(Color::White, PieceType::Queen), 1);
+        assert_eq!(kqk.pieces(Color::White), 1);
+        assert!(kqk.is_bare_king(Color::Black));
+        assert_eq!(kqk.mirrored().to_string(), "KvKQ");
+
+        let kqkp = Board::from_fen("4k3/4p3/8/8/8/8/8/3QK3 w - - 0 1").material_signature();
+        assert_ne!(kqkp, kqk);
+        assert_eq!(kqkp.to_string(), "KQvKP");
+        assert!(!kqkp.is_bare_king(Color::Black));
+    }
+
+    // Quiet positions
+
+    #[test]
+    fn hanging_piece_is_not_quiet() {
+        assert!(Board::from_fen(QUIET_MIDDLEGAME).is_quiet());
+        assert!(Board::from_fen(START_FEN).is_quiet());
+        // The rook on e5 can be taken for nothing
+        assert!(!Board::from_fen("4k3/8/8/4r3/8/8/8/4QK2 w - - 0 1").is_quiet());
+    }
+
+    #[test]
+    fn startpos_is_the_start_fen() {
+        let startpos = Board::from_fen("startpos");
+        let explicit = Board::from_fen(START_FEN);
+        assert!(startpos == explicit);
+        assert_eq!(startpos.hash(), explicit.hash());
+        assert_eq!(startpos.to_fen(), START_FEN);
+        assert!(Board::try_from_fen(" startpos ").is_ok());
+    }
+
+    // Piece letters
+
+    #[test]
+    fn piece_letters_round_trip() {
+        for letter in "PNBRQKpnbrqk".chars() {
+            let piece: Piece = letter.to_string().parse().unwrap();
+            assert_eq!(piece.to_string(), letter.to_string());
+            assert_eq!(piece.color == Color::White, letter.is_ascii_uppercase());
+        }
+        for bad in ["", "x", "Qq", "1"] {
+            assert_eq!(bad.parse::<Piece>(), Err(ParsePieceError(bad.to_string())));
+        }
+        for piece_type in PieceType::ALL {
+            let letter = piece_type.to_string();
+            assert_eq!(letter, piece_type.to_char().to_string());
+            assert_eq!(
+                PieceType::from_char(letter.chars().next().unwrap()),
+                Some(piece_type)
+            );
+        }
+        assert_eq!(Color::White.to_string(), "White");
+        assert_eq!(Color::Black.to_string(), "Black");
+    }
+
+    // Squares
+
+    #[test]
+    fn square_names_round_trip() {
+        // Name, index, row, column, rank, file
+        for (name, index, row, col, rank, file) in [
+            ("a1", 56, 7, 0, 0, 0),
+            ("h8", 7, 0, 7, 7, 7),
+            ("e4", 36, 4, 4, 3, 4),
+        ] {
+            let sq = square(name);
+            assert_eq!(sq.to_algebraic(), name);
+            assert_eq!((sq.index(), sq.row(), sq.col()), (index, row, col));
+            assert_eq!((sq.rank(), sq.file()), (rank, file));
+            assert_eq!(Square::from_index(index), Some(sq));
+            assert_eq!(Square::from_row_col(row, col), Some(sq));
+        }
+        for bad in ["", "i1", "a9", "a0", "e44", "E4"] {
+            assert_eq!(Square::from_algebraic(bad), None, "{}", bad);
+        }
+        assert_eq!(Square::from_index(64), None);
+    }
+
+    // Board rendering
+
+    #[test]
+    fn start_position_renders() {
+        let board = Board::from_fen(START_FEN);
+        assert_eq!(
+            board.to_ascii(),
+            "  a b c d e f g h\n\
+             8 r n b q k b n r 8\n\
+             7 p p p p p p p p 7\n\
+             6 . . . . . . . . 6\n\
+             5 . . . . . . . . 5\n\
+             4 . . . . . . . . 4\n\
+             3 . . . . . . . . 3\n\
+             2 P P P P P P P P 2\n\
+             1 R N B Q K B N R 1\n\
+             \x20 a b c d e f g h\n"
+        );
+        assert_eq!(
+            board.to_unicode(),
+            "  a b c d e f g h\n\
+             8 ♜ ♞ ♝ ♛ ♚ ♝ ♞ ♜ 8\n\
+             7 ♟ ♟ ♟ ♟ ♟ ♟ ♟ ♟ 7\n\
+             6 . . . . . . . . 6\n\
+             5 . . . . . . . . 5\n\
+             4 . . . . . . . . 4\n\
+             3 . . . . . . . . 3\n\
+             2 ♙ ♙ ♙ ♙ ♙ ♙ ♙ ♙ 2\n\
+             1 ♖ ♘ ♗ ♕ ♔ ♗ ♘ ♖ 1\n\
+             \x20 a b c d e f g h\n"
+        );
+    }
+
+    // Coordinate notation
+
+    #[test]
+    fn from_coords_reads_promotions() {
+        let queen = Move::from_coords("a7a8q").unwrap();
+        assert_eq!(
+            (queen.from_square(), queen.to_square()),
+            (square("a7"), square("a8"))
+        );
+        assert_eq!(queen.promotion, Some(PieceType::Queen));
+        let knight = Move::from_coords("a7a8n").unwrap();
+        assert_eq!(knight.promotion, Some(PieceType::Knight));
+        assert_eq!(knight.to_coords(), "a7a8n");
+        assert_eq!(Move::from_coords("a7a8").unwrap().promotion, None);
+        for bad in ["a7a8k", "a7a8p", "a7a8Q", "a7a8qq", "a7a"] {
+            assert_eq!(Move::from_coords(bad), None, "{}", bad);
+        }
+    }
+
+    // Move classification
+
+    #[test]
+    fn classifies_castling_en_passant_and_captures() {
+        let mv = |coords| Move::from_coords(coords).unwrap();
+        let board = Board::from_fen("r3k2r/8/8/3pP3/8/8/8/R3K2R w KQkq d6 0 1");
+        let castle = mv("e1h1");
+        assert!(board.is_castling(&castle));
+        assert!(!board.is_capture(&castle));
+        assert_eq!(board.classify(&castle), MoveKind::Castle);
+
+        let en_passant = mv("e5d6");
+        assert!(board.is_en_passant(&en_passant));
+        assert!(board.is_capture(&en_passant));
+        assert_eq!(board.classify(&en_passant), MoveKind::EnPassant);
+
+        let capture = mv("a1a8");
+        assert!(board.is_capture(&capture));
+        assert!(!board.is_castling(&capture) && !board.is_en_passant(&capture));
+        assert_eq!(board.classify(&capture), MoveKind::Capture);
+
+        let push = mv("e5e6");
+        assert!(!board.is_capture(&push) && !board.is_en_passant(&push));
+        assert_eq!(board.classify(&push), MoveKind::Quiet);
+    }
+
+    #[test]
+    fn fen_en_passant_target_allows_the_capture() {
+        let with_ep = Board::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1");
+        assert_eq!(with_ep.en_passant_square(), Some(square("d6")));
+        assert!(coords(&generate_moves(&with_ep)).contains(&"e5d6".to_string()));
+
+        let without = Board::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - - 0 1");
+        assert_eq!(without.en_passant_square(), None);
+        assert!(!coords(&generate_moves(&without)).contains(&"e5d6".to_string()));
+    }
+
+    // Analysis
+
+    #[test]
+    fn analyze_finds_a_knight_fork() {
+        // Nc7+ forks the king and the queen
+        let fen = "q3k3/8/8/1N6/8/8/8/4K3 w - - 0 1";
+        assert!(evaluate(&Board::from_fen(fen), &EvalParams::default()) < 0);
+        let analysis = analyze_core(fen, 4);
+        let best = analysis.best_move.unwrap();
+        assert_eq!(best.to_coords(), "b5c7");
+        assert_eq!(analysis.pv.first(), Some(&best));
+        assert_eq!(
+            analysis.pv.get(2).map(Move::to_coords).as_deref(),
+            Some("c7a8")
+        );
+        match analysis.score {
+            // Up a knight once the queen falls
+            EvalReport::Cp(cp) => assert!(cp > KNIGHT_VAL / 2, "{}", cp),
+            report => panic!("expected a centipawn score, got {:?}", report),
+        }
+    }
+
+    // Evaluation terms
+
+    #[test]
+    fn rook_on_an_open_file_beats_one_behind_its_pawn() {
+        let params = EvalParams::default();
+        let open = Board::from_fen("6k1/pp3ppp/8/8/8/8/PP3PPP/3R2K1 w - - 0 1");
+        let closed = Board::from_fen("6k1/pp3ppp/8/8/8/8/PP3PPP/R5K1 w - - 0 1");
+        assert_eq!(
+            evaluate_verbose(&open, &params).rook_placement,
+            params.rook_open_file_bonus
+        );
+        assert_eq!(evaluate_verbose(&closed, &params).rook_placement, 0);
+        assert!(evaluate(&open, &params) > evaluate(&closed, &params));
+    }
+
+    #[test]
+    fn intact_king_shield_beats_a_shattered_one() {
+        let params = EvalParams::default();
+        let intact = Board::from_fen("6k1/5ppp/8/8/8/8/5PPP/6K1 w - - 0 1");
+        // The same pawns pushed up the board, away from the king
+        let shattered = Board::from_fen("6k1/5ppp/8/8/5PPP/8/8/6K1 w - - 0 1");
+        assert!(
+            evaluate_verbose(&intact, &params).king_safety
+                > evaluate_verbose(&shattered, &params).king_safety
+        );
+        assert!(evaluate(&intact, &params) > evaluate(&shattered, &params));
+    }
+
+    #[test]
+    fn bishop_pair_scores_the_bonus() {
+        let params = EvalParams::default();
+        let pair = Board::from_fen("4k3/pppp4/8/8/8/8/PPPP4/2B1KB2 w - - 0 1");
+        let bishop_and_knight = Board::from_fen("4k3/pppp4/8/8/8/8/PPPP4/2B1KN2 w - - 0 1");
+        assert_eq!(
+            evaluate_verbose(&pair, &params).bishop_pair,
+            params.bishop_pair_bonus
+        );
+        assert_eq!(evaluate_verbose(&bishop_and_knight, &params).bishop_pair, 0);
+        assert!(evaluate(&pair, &params) > evaluate(&bishop_and_knight, &params));
+        // Black's pair counts against White
+        assert_eq!(
+            evaluate_verbose(&pair.mirrored(), &params).bishop_pair,
+            -params.bishop_pair_bonus
+        );
+    }
+
+    #[test]
+    fn eval_params_move_the_score_their_way() {
+        let base = EvalParams::default();
+        let score = |fen, params: &EvalParams| evaluate(&Board::from_fen(fen), params);
+
+        // White's extra bishop pair counts for more with a bigger bonus
+        let pair = "4k3/pppp4/8/8/8/8/PPPP4/2B1KB2 w - - 0 1";
+        let bigger_pair = EvalParams {
+            bishop_pair_bonus: base.bishop_pair_bonus + 40,
+            ..base.clone()
+        };
+        assert_eq!(score(pair, &bigger_pair) - score(pair, &base), 40);
+
+        // White's doubled c-pawns cost more with a bigger penalty
+        let doubled = "4k3/ppp5/8/8/8/2P5/P1P5/4K3 w - - 0 1";
+        let harsher = EvalParams {
+            doubled_pawn_penalty: base.doubled_pawn_penalty + 30,
+            ..base.clone()
+        };
+        assert!(score(doubled, &harsher) < score(doubled, &base));
+
+        // An extra white queen is worth more when queens are
+        let extra_queen = "4k3/8/8/8/8/8/8/3QK3 w - - 0 1";
+        let mut values = base.piece_values;
+        values[PieceType::Queen.index()] += 100;
+        let dearer_queens = EvalParams {
+            piece_values: values,
+            ..base.clone()
+        };
+        assert_eq!(
+            score(extra_queen, &dearer_queens) - score(extra_queen, &base),
+            100
+        );
+
+        // White's queen out in the open is rewarded once mobility counts
+        let active = "4k3/pppppppp/8/8/3Q4/8/PPPPPPPP/4K3 w - - 0 1";
+        let mobile = EvalParams {
+            mobility_weight: 5,
+            ..base.clone()
+        };
+        assert!(score(active, &mobile) > score(active, &base));
+    }
+
+    // Material
+
+    #[test]
+    fn start_position_material_is_level() {
+        assert_eq!(material_balance_core(START_FEN), 0);
+        let counts = Board::from_fen(START_FEN).piece_counts();
+        assert_eq!(counts.white, [8, 2, 2, 2, 1, 1]);
+        assert_eq!(counts.black, counts.white);
+        assert_eq!(counts.get(Color::Black, PieceType::Knight), 2);
+        // Without Black's queen White is a queen up
+        let no_black_queen = "rnb1kbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        assert_eq!(material_balance_core(no_black_queen), QUEEN_VAL);
+    }
+
+    #[test]
+    fn start_position_has_sixteen_pieces_a_side() {
+        let board = Board::from_fen(START_FEN);
+        assert_eq!(board.pieces().count(), 32);
+        for color in [Color::White, Color::Black] {
+            assert_eq!(board.pieces_of(color).count(), 16);
+            assert!(board.pieces_of(color).all(|(_, p)| p.color == color));
+        }
+        // a8 comes first
+        let (sq, piece) = board.pieces().next().unwrap();
+        assert_eq!(sq, square("a8"));
+        assert_eq!(piece.to_string(), "r");
+    }
+
+    #[test]
+    fn piece_values_rise_from_pawn_to_king() {
+        let values: Vec<i32> = PieceType::ALL.iter().map(|pt| pt.value()).collect();
+        assert!(values.windows(2).all(|w| w[0] < w[1]), "{:?}", values);
+        assert_eq!(PieceType::Pawn.value(), PAWN_VAL);
+        assert_eq!(PieceType::King.value(), KING_VAL);
+    }
+
+    // Colours
+
+    #[test]
+    fn opponent_and_forward_direction() {
+        assert_eq!(Color::White.opponent(), Color::Black);
+        assert_eq!(Color::Black.opponent(), Color::White);
+        // Row 0 is rank 8, so White pushes towards lower rows
+        assert_eq!(Color::White.forward_dir(), -1);
+        assert_eq!(Color::Black.forward_dir(), 1);
+        let board = play(START_FEN, &["e2e3", "d7d6"]);
+        assert_eq!(
+            board.piece_at(square("e3")).map(|p| p.color),
+            Some(Color::White)
+        );
+        let e2 = square("e2");
+        let e3 = square("e3");
+        assert_eq!(
+            e3.row() as i32 - e2.row() as i32,
+            Color::White.forward_dir()
+        );
+        let d7 = square("d7");
+        let d6 = square("d6");
+        assert_eq!(
+            d6.row() as i32 - d7.row() as i32,
+            Color::Black.forward_dir()
+        );
+    }
+
+    #[test]
+    fn start_position_accessors() {
+        let board = Board::from_fen("startpos");
+        assert_eq!(board.side_to_move(), Color::White);
+        assert_eq!(board.castling_rights(), [true; 4]);
+        assert_eq!(board.en_passant_square(), None);
+        assert_eq!(board.halfmove_clock(), 0);
+        let board = play(START_FEN, &["g1f3"]);
+        assert_eq!(board.side_to_move(), Color::Black);
+        assert_eq!(board.halfmove_clock(), 1);
+    }
+
+    #[test]
+    fn knight_attacks_in_the_centre_and_the_corner() {
+        let targets = |name| {
+            let mut names: Vec<String> = BitIter(knight_attacks(square(name).index()))
+                .map(|sq| Square(sq as u8).to_algebraic())
+                .collect();
+            names.sort();
+            names
+        };
+        assert_eq!(
+            targets("e4"),
+            ["c3", "c5", "d2", "d6", "f2", "f6", "g3", "g5"]
+        );
+        assert_eq!(targets("a1"), ["b3", "c2"]);
+        assert_eq!(targets("h8"), ["f7", "g6"]);
+        assert_eq!(targets("b1"), ["a3", "c3", "d2"]);
+    }
+
+    // Pawn pushes
+
+    #[test]
+    fn double_push_needs_a_clear_path_from_the_home_rank() {
+        let pushes = |fen, from: &str| {
+            let board = Board::from_fen(fen);
+            let moves: Vec<Move> = generate_moves(&board)
+                .into_iter()
+                .filter(|m| m.from_square() == square(from))
+                .collect();
+            coords(&moves)
+        };
+        // A piece on the skipped square stops both pushes
+        assert!(pushes("4k3/8/8/8/8/4n3/4P3/4K3 w - - 0 1", "e2").is_empty());
+        // A piece on the landing square stops only the double push
+        assert_eq!(pushes("4k3/8/8/8/4n3/8/4P3/4K3 w - - 0 1", "e2"), ["e2e3"]);
+        // Off the home rank a pawn moves one square
+        assert_eq!(pushes("4k3/8/8/8/8/4P3/8/4K3 w - - 0 1", "e3"), ["e3e4"]);
+        assert_eq!(pushes("4k3/8/3p4/8/8/8/8/4K3 b - - 0 1", "d6"), ["d6d5"]);
+        assert_eq!(
+            pushes("4k3/3p4/8/8/8/8/8/4K3 b - - 0 1", "d7"),
+            ["d7d5", "d7d6"]
+        );
+    }
+
+    // Chess960 castling
+
+    #[test]
+    fn chess960_castles_from_non_standard_files() {
+        let start = chess960_fen(0);
+        assert!(start.starts_with("bbqnnrkr/"));
+        assert_eq!(Board::from_fen(&start).castling_rights(), [true; 4]);
+
+        // King on f1 with rooks on b1 and g1
+        let fen = "4k3/8/8/8/8/8/8/1R3KR1 w GB - 0 1";
+        let board = Board::from_fen(fen);
+        let castles: Vec<Move> = generate_moves(&board)
+            .into_iter()
+            .filter(|m| board.is_castling(m))
+            .collect();
+        assert_eq!(coords(&castles), ["f1b1", "f1g1"]);
+        let mut uci: Vec<String> = castles.iter().map(|m| board.move_to_uci(m)).collect();
+        uci.sort();
+        assert_eq!(uci, ["f1c1", "f1g1"]);
+
+        // Queenside: the king lands on c1 and the rook on d1
+        let board = play(fen, &["f1b1"]);
+        assert_eq!(board.to_fen(), "4k3/8/8/8/8/8/8/2KR2R1 b - - 1 1");
+        // Kingside: the king steps onto g1 and the rook jumps to f1
+        let board = play(fen, &["f1g1"]);
+        assert_eq!(board.to_fen(), "4k3/8/8/8/8/8/8/1R3RK1 b - - 1 1");
+    }
 }
@@ -0,0 +1,52 @@
+use chess_engine::{evaluate, generate_moves, Board, EvalParams};
+
+/// Positions checked as they are and as the starting points of random games.
+const POSITIONS: &[&str] = &[
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/3P1N2/PPP2PPP/RNBQK2R b KQkq - 0 4",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+];
+
+const GAMES_PER_POSITION: usize = 20;
+const MAX_GAME_PLIES: usize = 120;
+
+/// Every position scores as the exact negation of its mirror image, over
+/// the positions above and the positions of seeded random games played
+/// from them, with the default and the preset weights.
+#[test]
+fn evaluation_is_colour_symmetric() {
+    let params = [
+        ("default", EvalParams::default()),
+        ("aggressive", EvalParams::aggressive()),
+        ("positional", EvalParams::positional()),
+    ];
+    let mut rng = 0x9E37_79B9_7F4A_7C15u64;
+    for fen in POSITIONS {
+        for game in 0..GAMES_PER_POSITION {
+            let mut board = Board::from_fen(fen);
+            // Every game starts from the position itself
+            let plies = if game == 0 { 0 } else { MAX_GAME_PLIES };
+            for ply in 0..=plies {
+                for (name, params) in &params {
+                    assert_eq!(
+                        evaluate(&board, params),
+                        -evaluate(&board.mirrored(), params),
+                        "{} weights on {}",
+                        name,
+                        board.to_fen()
+                    );
+                }
+                let moves = generate_moves(&board);
+                if moves.is_empty() || ply == plies {
+                    break;
+                }
+                rng ^= rng << 13;
+                rng ^= rng >> 7;
+                rng ^= rng << 17;
+                board.make_move(&moves[(rng % moves.len() as u64) as usize]);
+            }
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use chess_engine::{perft, Board};
+
+/// The standard perft positions with their published node counts by depth.
+/// Together they cover castling, en passant, promotions, pins and checks.
+const SUITE: &[(&str, &[(u8, u64)])] = &[
+    (
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        &[(1, 20), (2, 400), (3, 8_902), (4, 197_281), (5, 4_865_609)],
+    ),
+    (
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        &[(1, 48), (2, 2_039), (3, 97_862), (4, 4_085_603)],
+    ),
+    (
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        &[(1, 14), (2, 191), (3, 2_812), (4, 43_238), (5, 674_624)],
+    ),
+    (
+        "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        &[(1, 6), (2, 264), (3, 9_467), (4, 422_333)],
+    ),
+    (
+        "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        &[(1, 44), (2, 1_486), (3, 62_379), (4, 2_103_487)],
+    ),
+    (
+        "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+        &[(1, 46), (2, 2_079), (3, 89_890), (4, 3_894_594)],
+    ),
+];
+
+/// Rows above this many nodes take too long in a debug build to run by
+/// default; `cargo test --release -- --ignored` runs them.
+const QUICK_NODES: u64 = 1_000_000;
+
+fn check_rows(include: impl Fn(u64) -> bool) {
+    for (fen, counts) in SUITE {
+        let board = Board::from_fen(fen);
+        for &(depth, expected) in *counts {
+            if include(expected) {
+                assert_eq!(perft(&board, depth), expected, "depth {} of {}", depth, fen);
+            }
+        }
+    }
+}
+
+#[test]
+fn perft_suite() {
+    check_rows(|nodes| nodes <= QUICK_NODES);
+}
+
+#[test]
+#[ignore]
+fn perft_suite_deep() {
+    check_rows(|nodes| nodes > QUICK_NODES);
+}